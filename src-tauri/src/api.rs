@@ -1,56 +1,282 @@
-use serde_json::json;
+use std::time::Instant;
+use serde_json::{json, Value};
+use tauri::Manager;
+use crate::cache::CacheSlot;
+use crate::generation::run_cancellable;
+use crate::providers::error::ProviderError;
+use crate::providers::http::send_with_retry;
+use crate::providers::openai::{read_sse_stream, StreamedCompletion};
+use crate::providers::{prepare_images, record_usage, resolve_api_key, ChatMessage, Usage};
+use crate::streaming::{ensure_request_id, StreamSink};
 
-#[tauri::command]
-pub async fn query_deepseek(api_key: String, base_url: String, model: String, prompt: String) -> Result<String, String> {
-    println!("[api.query_deepseek] called with base_url='{}', model='{}', prompt_len={} (api_key hidden)", base_url, model, prompt.len());
-    let url = if base_url.trim().is_empty() {
-        "https://api.deepseek.com/chat/completions".to_string()
-    } else {
-        base_url.trim().to_string()
-    };
+/// Uses the message history when given (system/user/assistant roles, optional images),
+/// else a single user turn from `prompt`. Reasoning is dropped from assistant turns.
+async fn build_messages(prompt: &str, messages: Option<Vec<ChatMessage>>) -> Result<Vec<Value>, String> {
+    match messages.filter(|m| !m.is_empty()) {
+        Some(mut list) => {
+            for m in list.iter_mut() {
+                crate::reasoning::strip_history(&m.role, &mut m.content);
+            }
+            prepare_images(&mut list).await?;
+            Ok(list.iter().map(crate::providers::openai::message_json).collect())
+        }
+        None => Ok(vec![json!({"role": "user", "content": prompt})]),
+    }
+}
 
-    let mdl = if model.trim().is_empty() { "deepseek-chat".to_string() } else { model };
+/// Sets temperature/top_p/max_tokens from the call, falling back to the generation settings.
+fn apply_generation_controls(
+    app: &tauri::AppHandle,
+    body: &mut Value,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: Option<i64>,
+) {
+    let settings = crate::db::load_settings(app.clone()).ok();
+    let temperature = temperature.or(settings.as_ref().and_then(|s| s.temperature));
+    let top_p = top_p.or(settings.as_ref().and_then(|s| s.top_p));
+    let max_tokens = max_tokens.or(settings.as_ref().and_then(|s| s.max_tokens));
+    if let Some(t) = temperature { body["temperature"] = json!(t); }
+    if let Some(p) = top_p { body["top_p"] = json!(p); }
+    // -1 means "unlimited" in settings; cloud APIs reject non-positive values
+    if let Some(n) = max_tokens.filter(|n| *n > 0) { body["max_tokens"] = json!(n); }
+}
 
-    let client = reqwest::Client::new();
-    let mut req = client.post(&url);
-    if !api_key.trim().is_empty() {
-        req = req.header("Authorization", format!("Bearer {}", api_key));
-    }
+#[tauri::command]
+pub async fn query_deepseek(
+    window: tauri::Window,
+    api_key: String,
+    base_url: String,
+    model: String,
+    prompt: String,
+    messages: Option<Vec<ChatMessage>>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: Option<i64>,
+    stream: Option<bool>,
+    request_id: Option<String>,
+    response_schema: Option<Value>,
+    repair_attempts: Option<u32>,
+    project_id: Option<i64>,
+    bypass_cache: Option<bool>,
+) -> Result<String, ProviderError> {
+    let msg_len = messages.as_ref().map(|m| m.len()).unwrap_or(0);
+    println!("[api.query_deepseek] called with base_url='{}', model='{}', prompt_len={}, messages_len={}, stream={:?} (api_key hidden)", base_url, model, prompt.len(), msg_len, stream);
+    let request_id = ensure_request_id(request_id);
+    let streaming = stream.unwrap_or(false);
+    let result: Result<String, ProviderError> = async {
+        let url = if base_url.trim().is_empty() {
+            "https://api.deepseek.com/chat/completions".to_string()
+        } else {
+            base_url.trim().to_string()
+        };
+
+        let mdl = if model.trim().is_empty() { "deepseek-chat".to_string() } else { model };
 
-    let response = req
-        .json(&json!({
+        let mut msgs = build_messages(&prompt, messages).await?;
+        // DeepSeek has JSON mode but no schema enforcement: describe the schema in a system message
+        if let Some(schema) = &response_schema {
+            msgs.insert(0, json!({"role": "system", "content": crate::schema::instruction(schema)}));
+        }
+        let mut body = json!({
             "model": mdl,
-            "messages": [{"role": "user", "content": prompt}],
-            "temperature": 0.7
-        }))
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+            "messages": msgs,
+        });
+        if response_schema.is_some() {
+            body["response_format"] = json!({"type": "json_object"});
+        }
+        apply_generation_controls(window.app_handle(), &mut body, temperature, top_p, max_tokens);
 
-    response.text().await.map_err(|e| e.to_string())
+        let started = Instant::now();
+        let api_key = resolve_api_key(window.app_handle(), api_key)?;
+        let (raw, cached) = send_chat_completion(&window, "deepseek", &url, &api_key, body, streaming, &request_id, response_schema, repair_attempts, bypass_cache).await?;
+        if !cached {
+            record_raw_usage(window.app_handle(), project_id, "deepseek", &mdl, &raw, started);
+        }
+        Ok(raw)
+    }
+    .await;
+    fail_stream(&window, streaming, &request_id, &result);
+    result
 }
 
 #[tauri::command]
-pub async fn query_openai(api_key: String, base_url: String, model: String, prompt: String) -> Result<String, String> {
-    println!("[api.query_openai] called with base_url='{}', model='{}', prompt_len={} (api_key hidden)", base_url, model, prompt.len());
-    let base = base_url.trim_end_matches('/').to_string();
-    let url = format!("{}/chat/completions", base);
-
-    let client = reqwest::Client::new();
-    let mut req = client.post(&url);
-    if !api_key.trim().is_empty() {
-        req = req.header("Authorization", format!("Bearer {}", api_key));
-    }
-    let response = req
-        .json(&json!({
+pub async fn query_openai(
+    window: tauri::Window,
+    api_key: String,
+    base_url: String,
+    model: String,
+    prompt: String,
+    messages: Option<Vec<ChatMessage>>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: Option<i64>,
+    stream: Option<bool>,
+    request_id: Option<String>,
+    response_schema: Option<Value>,
+    repair_attempts: Option<u32>,
+    project_id: Option<i64>,
+    bypass_cache: Option<bool>,
+) -> Result<String, ProviderError> {
+    let msg_len = messages.as_ref().map(|m| m.len()).unwrap_or(0);
+    println!("[api.query_openai] called with base_url='{}', model='{}', prompt_len={}, messages_len={}, stream={:?} (api_key hidden)", base_url, model, prompt.len(), msg_len, stream);
+    let request_id = ensure_request_id(request_id);
+    let streaming = stream.unwrap_or(false);
+    let result: Result<String, ProviderError> = async {
+        let base = base_url.trim_end_matches('/').to_string();
+        let url = format!("{}/chat/completions", base);
+
+        let mut body = json!({
             "model": model,
-            "messages": [{"role": "user", "content": prompt}],
-            "temperature": 0.7
-        }))
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+            "messages": build_messages(&prompt, messages).await?,
+        });
+        apply_generation_controls(window.app_handle(), &mut body, temperature, top_p, max_tokens);
+        if let Some(schema) = &response_schema {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {"name": "response", "schema": schema}
+            });
+        }
+
+        let started = Instant::now();
+        let api_key = resolve_api_key(window.app_handle(), api_key)?;
+        let (raw, cached) = send_chat_completion(&window, "openai", &url, &api_key, body, streaming, &request_id, response_schema, repair_attempts, bypass_cache).await?;
+        if !cached {
+            record_raw_usage(window.app_handle(), project_id, "openai", &model, &raw, started);
+        }
+        Ok(raw)
+    }
+    .await;
+    fail_stream(&window, streaming, &request_id, &result);
+    result
+}
+
+/// Records usage found in a raw response body (OpenAI `usage`, Ollama counters or llama-server `timings`).
+pub(crate) fn record_raw_usage(app: &tauri::AppHandle, project_id: Option<i64>, provider: &str, model: &str, raw: &str, started: Instant) {
+    let usage = serde_json::from_str::<Value>(raw).ok().as_ref().and_then(Usage::from_body);
+    record_usage(app, project_id, provider, model, usage.as_ref(), started.elapsed().as_millis() as u64);
+}
+
+/// Ends a failed streaming request with `chat_stream_done` carrying the error.
+fn fail_stream(window: &tauri::Window, stream: bool, request_id: &str, result: &Result<String, ProviderError>) {
+    if let (true, Err(e)) = (stream, result) {
+        StreamSink::new(window.clone(), Some(request_id.to_string())).fail(e);
+    }
+}
+
+/// Returns the raw response body and whether it came from the response cache.
+async fn send_chat_completion(
+    window: &tauri::Window,
+    provider: &str,
+    url: &str,
+    api_key: &str,
+    body: Value,
+    stream: bool,
+    request_id: &str,
+    response_schema: Option<Value>,
+    repair_attempts: Option<u32>,
+    bypass_cache: Option<bool>,
+) -> Result<(String, bool), ProviderError> {
+    let cache = CacheSlot::for_body(window.app_handle(), bypass_cache, url, &body);
+    if let Some(hit) = cache.as_ref().and_then(|c| c.get(window.app_handle())) {
+        if stream {
+            replay_stream(window, request_id, &hit);
+        }
+        return Ok((hit, true));
+    }
+    let model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+    let result = match &response_schema {
+        // Structured answers are streamed only once validated, so a rejected or repaired
+        // answer never reaches streaming listeners
+        Some(schema) => {
+            let send = |body: Value, _attempt: u32| post_chat_completion(window, url, api_key, body, false, request_id);
+            let result = run_cancellable(
+                window.app_handle(),
+                request_id,
+                crate::schema::send_validated(body, schema, "/choices/0/message/content", repair_attempts.unwrap_or(0), send),
+            )
+            .await;
+            if let (true, Ok(raw)) = (stream, &result) {
+                replay_stream(window, request_id, raw);
+            }
+            result
+        }
+        None => {
+            run_cancellable(
+                window.app_handle(),
+                request_id,
+                post_chat_completion(window, url, api_key, body, stream, request_id),
+            )
+            .await
+        }
+    };
+    if let (Some(cache), Ok(raw)) = (&cache, &result) {
+        cache.put(window.app_handle(), provider, &model, raw);
+    }
+    result.map(|raw| (raw, false))
+}
+
+/// Emits a finished response body as token/done events, so streaming listeners see the usual events.
+fn replay_stream(window: &tauri::Window, request_id: &str, raw: &str) {
+    let v: Value = serde_json::from_str(raw).unwrap_or(Value::Null);
+    let sink = StreamSink::new(window.clone(), Some(request_id.to_string()));
+    sink.reasoning(v.pointer("/choices/0/message/reasoning_content").and_then(|r| r.as_str()).unwrap_or(""));
+    sink.token(v.pointer("/choices/0/message/content").and_then(|c| c.as_str()).unwrap_or(""));
+    sink.done(v.pointer("/choices/0/finish_reason").and_then(|f| f.as_str()).map(str::to_string), v.get("usage").cloned());
+}
+
+async fn post_chat_completion(
+    window: &tauri::Window,
+    url: &str,
+    api_key: &str,
+    mut body: Value,
+    stream: bool,
+    request_id: &str,
+) -> Result<String, ProviderError> {
+    if stream {
+        body["stream"] = json!(true);
+        // Ask for a trailing usage chunk; servers that do not know the option ignore it
+        body["stream_options"] = json!({"include_usage": true});
+    }
+
+    // Error statuses become typed errors (429/5xx are retried) instead of being returned as text
+    let client = crate::providers::http::client(window.app_handle())?;
+    let response = send_with_retry(|| {
+        let mut req = client.post(url).json(&body);
+        if !api_key.trim().is_empty() {
+            req = req.header("Authorization", format!("Bearer {}", api_key));
+        }
+        req
+    })
+    .await?;
 
-    response.text().await.map_err(|e| e.to_string())
+    if !stream {
+        let raw = response.text().await?;
+        return Ok(crate::reasoning::split_raw(raw, "/choices/0/message", "reasoning_content"));
+    }
+
+    let sink = StreamSink::new(window.clone(), Some(request_id.to_string()));
+    let streamed = read_sse_stream(response, &sink).await?;
+    sink.done(streamed.finish_reason.clone(), streamed.usage.clone());
+    Ok(streamed_body(streamed, sink.request_id()))
 }
 
+/// Builds a non-streaming `/chat/completions` body from a read stream, so callers parse both modes alike.
+pub(crate) fn streamed_body(streamed: StreamedCompletion, id: &str) -> String {
+    let (content, inline) = crate::reasoning::split(&streamed.content);
+    let reasoning = crate::reasoning::join(Some(streamed.reasoning), inline);
+    let mut body = json!({
+        "id": id,
+        "model": streamed.model,
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content, "reasoning_content": reasoning},
+            "finish_reason": streamed.finish_reason
+        }],
+        "usage": streamed.usage
+    });
+    if let Some(timings) = streamed.timings {
+        body["timings"] = timings;
+    }
+    body.to_string()
+}
@@ -0,0 +1,125 @@
+use std::io::Cursor;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+
+/// Largest image file accepted as input.
+pub const MAX_INPUT_BYTES: usize = 20 * 1024 * 1024;
+/// Images are downscaled so the longer side fits this many pixels.
+pub const MAX_DIMENSION: u32 = 2048;
+/// Largest encoded image sent to a provider.
+pub const MAX_OUTPUT_BYTES: usize = 4 * 1024 * 1024;
+/// Images allowed in one request.
+pub const MAX_IMAGES: usize = 8;
+const JPEG_QUALITY: u8 = 85;
+
+/// An image attached to a chat message: a local file path or base64 data (e.g. a pasted screenshot).
+/// Before sending, `prepare` replaces both forms with size-limited base64 `data` and a `mime_type`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageAttachment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+impl ImageAttachment {
+    /// Base64 payload; empty until `prepare` has run.
+    pub fn base64(&self) -> &str {
+        self.data.as_deref().unwrap_or("")
+    }
+
+    pub fn mime(&self) -> &str {
+        self.mime_type.as_deref().unwrap_or("image/png")
+    }
+
+    /// `data:` URL used by OpenAI-style `image_url` parts.
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime(), self.base64())
+    }
+}
+
+/// Loads, validates and (if needed) downscales every attachment in place.
+/// Image decoding is CPU-bound, so it runs on the blocking pool.
+pub async fn prepare(images: Vec<ImageAttachment>) -> Result<Vec<ImageAttachment>, String> {
+    if images.is_empty() {
+        return Ok(images);
+    }
+    if images.len() > MAX_IMAGES {
+        return Err(format!("Too many images: {} (at most {} per request)", images.len(), MAX_IMAGES));
+    }
+    tokio::task::spawn_blocking(move || images.iter().map(prepare_one).collect())
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn prepare_one(image: &ImageAttachment) -> Result<ImageAttachment, String> {
+    let bytes = match (&image.path, &image.data) {
+        (Some(path), _) if !path.trim().is_empty() => {
+            let len = std::fs::metadata(path).map_err(|e| format!("Cannot read image '{}': {}", path, e))?.len();
+            if len as usize > MAX_INPUT_BYTES {
+                return Err(format!("Image '{}' is too large ({} MB, limit {} MB)", path, len / (1024 * 1024), MAX_INPUT_BYTES / (1024 * 1024)));
+            }
+            std::fs::read(path).map_err(|e| format!("Cannot read image '{}': {}", path, e))?
+        }
+        (_, Some(data)) => {
+            // Accept a full data URL as well as bare base64
+            let raw = data.split_once(";base64,").map(|(_, b)| b).unwrap_or(data);
+            let bytes = BASE64.decode(raw.trim()).map_err(|e| format!("Invalid base64 image: {}", e))?;
+            if bytes.len() > MAX_INPUT_BYTES {
+                return Err(format!("Image is too large (limit {} MB)", MAX_INPUT_BYTES / (1024 * 1024)));
+            }
+            bytes
+        }
+        _ => return Err("Image attachment needs a path or data".to_string()),
+    };
+    let (bytes, format) = shrink(bytes)?;
+    Ok(ImageAttachment {
+        path: None,
+        data: Some(BASE64.encode(&bytes)),
+        mime_type: Some(format.to_mime_type().to_string()),
+    })
+}
+
+/// Returns the image unchanged when it is within limits, else re-encodes it smaller.
+fn shrink(bytes: Vec<u8>) -> Result<(Vec<u8>, ImageFormat), String> {
+    let reader = ImageReader::new(Cursor::new(&bytes)).with_guessed_format().map_err(|e| e.to_string())?;
+    let format = reader.format().ok_or_else(|| "Unsupported image format".to_string())?;
+    let supported = matches!(format, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Gif);
+    let (w, h) = reader.into_dimensions().map_err(|e| format!("Cannot decode image: {}", e))?;
+    if supported && w.max(h) <= MAX_DIMENSION && bytes.len() <= MAX_OUTPUT_BYTES {
+        return Ok((bytes, format));
+    }
+
+    let img = image::load_from_memory(&bytes).map_err(|e| format!("Cannot decode image: {}", e))?;
+    let mut limit = MAX_DIMENSION;
+    loop {
+        let resized = if w.max(h) > limit { img.resize(limit, limit, image::imageops::FilterType::Lanczos3) } else { img.clone() };
+        let (out, out_format) = encode(&resized)?;
+        if out.len() <= MAX_OUTPUT_BYTES {
+            let (rw, rh) = resized.dimensions();
+            println!("[attachments.shrink] {}x{} ({} KB) -> {}x{} ({} KB)", w, h, bytes.len() / 1024, rw, rh, out.len() / 1024);
+            return Ok((out, out_format));
+        }
+        if limit <= 256 {
+            return Err("Image is still too large after downscaling".to_string());
+        }
+        limit /= 2;
+    }
+}
+
+/// PNG keeps transparency; everything else becomes JPEG.
+fn encode(img: &DynamicImage) -> Result<(Vec<u8>, ImageFormat), String> {
+    let mut out = Vec::new();
+    if img.color().has_alpha() {
+        img.write_to(&mut Cursor::new(&mut out), ImageFormat::Png).map_err(|e| e.to_string())?;
+        Ok((out, ImageFormat::Png))
+    } else {
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY);
+        img.to_rgb8().write_with_encoder(encoder).map_err(|e| e.to_string())?;
+        Ok((out, ImageFormat::Jpeg))
+    }
+}
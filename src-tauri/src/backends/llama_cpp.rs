@@ -1,10 +1,14 @@
 pub mod server;
 pub mod setup;
+pub mod status;
+pub mod launch;
+pub mod router;
+pub mod watchdog;
 pub mod models;
 pub mod query;
 pub mod download;
 
-pub use server::{start_llamacpp_server, stop_llamacpp_server};
+pub use server::{start_llamacpp_server, stop_llamacpp_server, LlamaServerState};
 pub use setup::ensure_dirs_setup;
 pub use models::{model_exists, resolve_model_path, list_models};
 pub use query::query_llamacpp;
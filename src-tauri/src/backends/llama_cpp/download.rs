@@ -28,11 +28,7 @@ pub async fn download_model_file(
         )
         .unwrap();
 
-    // Build HTTP client
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0")
-        .build()
-        .map_err(|e| format!("Ошибка создания HTTP клиента: {}", e))?;
+    let client = crate::providers::http::client(&app)?;
 
     println!("[llama_cpp.download_model_file] sending GET request");
     let response = client
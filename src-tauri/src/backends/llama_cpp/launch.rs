@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+/// Context size used when a model has no launch profile (or the profile leaves it unset).
+pub const DEFAULT_CTX_SIZE: u32 = 2048;
+
+/// KV cache types accepted by llama-server `--cache-type-k/-v`.
+const CACHE_TYPES: &[&str] = &["f32", "f16", "bf16", "q8_0", "q4_0", "q4_1", "iq4_nl", "q5_0", "q5_1"];
+
+/// Options the app always sets itself; extra args may not override them.
+const MANAGED_FLAGS: &[&str] = &["-m", "--model", "-hf", "-hfr", "--hf-repo", "--host", "--port"];
+
+/// Smallest per-slot context accepted when `parallel` splits `ctx_size` between slots.
+const MIN_SLOT_CTX: u32 = 256;
+
+/// llama-server launch options saved per model file. Unset fields keep the defaults:
+/// `-c 2048`, all layers on GPU builds and none on CPU builds, llama-server defaults otherwise.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LaunchProfile {
+    /// `-c`; 0 means the model's trained context.
+    pub ctx_size: Option<u32>,
+    /// `-ngl`
+    pub gpu_layers: Option<u32>,
+    /// `-t`
+    pub threads: Option<u32>,
+    /// `-b`
+    pub batch_size: Option<u32>,
+    /// `-ub`; cannot exceed `batch_size`.
+    pub ubatch_size: Option<u32>,
+    /// `--flash-attn on|off`; on is required by a quantized V cache. Unset leaves llama-server's auto mode.
+    pub flash_attn: Option<bool>,
+    /// `--mmap` / `--no-mmap`.
+    pub mmap: Option<bool>,
+    /// `--mlock`
+    pub mlock: Option<bool>,
+    /// `-ctk`, e.g. "f16" or "q8_0".
+    pub cache_type_k: Option<String>,
+    /// `-ctv`
+    pub cache_type_v: Option<String>,
+    /// `-np`: parallel slots sharing the context.
+    pub parallel: Option<u32>,
+    /// Further arguments, split on whitespace; double quotes group words.
+    pub extra_args: Option<String>,
+}
+
+/// The flags a profile field controls, so extra args cannot set them a second time.
+fn field_flags(p: &LaunchProfile) -> Vec<&'static str> {
+    let mut flags = Vec::new();
+    if p.ctx_size.is_some() { flags.extend(["-c", "--ctx-size"]); }
+    if p.gpu_layers.is_some() { flags.extend(["-ngl", "--gpu-layers", "--n-gpu-layers"]); }
+    if p.threads.is_some() { flags.extend(["-t", "--threads"]); }
+    if p.batch_size.is_some() { flags.extend(["-b", "--batch-size"]); }
+    if p.ubatch_size.is_some() { flags.extend(["-ub", "--ubatch-size"]); }
+    if p.flash_attn.is_some() { flags.extend(["-fa", "--flash-attn"]); }
+    if p.mmap.is_some() { flags.extend(["--mmap", "--no-mmap"]); }
+    if p.mlock.is_some() { flags.push("--mlock"); }
+    if p.cache_type_k.is_some() { flags.extend(["-ctk", "--cache-type-k"]); }
+    if p.cache_type_v.is_some() { flags.extend(["-ctv", "--cache-type-v"]); }
+    if p.parallel.is_some() { flags.extend(["-np", "--parallel"]); }
+    flags
+}
+
+/// Splits free-form arguments on whitespace, keeping double-quoted parts together.
+pub fn split_args(raw: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut has_arg = false;
+    for c in raw.chars() {
+        match c {
+            '"' => { quoted = !quoted; has_arg = true; }
+            c if c.is_whitespace() && !quoted => {
+                if has_arg { args.push(std::mem::take(&mut current)); }
+                has_arg = false;
+            }
+            c => { current.push(c); has_arg = true; }
+        }
+    }
+    if quoted {
+        return Err("Extra args: unclosed quote".to_string());
+    }
+    if has_arg { args.push(current); }
+    Ok(args)
+}
+
+impl LaunchProfile {
+    /// Rejects values llama-server would refuse and options that contradict each other.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("threads", self.threads), ("batch_size", self.batch_size), ("ubatch_size", self.ubatch_size), ("parallel", self.parallel)] {
+            if value == Some(0) {
+                return Err(format!("{} must be at least 1", name));
+            }
+        }
+        if let (Some(b), Some(ub)) = (self.batch_size, self.ubatch_size) {
+            if ub > b {
+                return Err(format!("ubatch_size ({}) cannot exceed batch_size ({})", ub, b));
+            }
+        }
+        for (name, value) in [("cache_type_k", &self.cache_type_k), ("cache_type_v", &self.cache_type_v)] {
+            if let Some(t) = value {
+                if !CACHE_TYPES.contains(&t.as_str()) {
+                    return Err(format!("{} '{}' is not one of: {}", name, t, CACHE_TYPES.join(", ")));
+                }
+            }
+        }
+        let quantized_v = self.cache_type_v.as_deref().is_some_and(|t| !matches!(t, "f32" | "f16" | "bf16"));
+        if quantized_v && !self.flash_attn.unwrap_or(false) {
+            return Err("A quantized V cache (cache_type_v) requires flash_attn".to_string());
+        }
+        if let (Some(ctx), Some(np)) = (self.ctx_size.filter(|c| *c > 0), self.parallel) {
+            if ctx / np < MIN_SLOT_CTX {
+                return Err(format!("ctx_size {} split across {} parallel slots leaves under {} tokens per slot", ctx, np, MIN_SLOT_CTX));
+            }
+        }
+        let owned = field_flags(self);
+        for arg in split_args(self.extra_args.as_deref().unwrap_or(""))? {
+            let flag = arg.split('=').next().unwrap_or("");
+            if MANAGED_FLAGS.contains(&flag) {
+                return Err(format!("Extra args cannot set {}: the app manages it", flag));
+            }
+            if owned.contains(&flag) {
+                return Err(format!("Extra args set {} which the profile already sets", flag));
+            }
+        }
+        Ok(())
+    }
+
+    /// Arguments for everything except `-c` and `-ngl`, which need the variant defaults.
+    pub fn args(&self) -> Result<Vec<String>, String> {
+        let mut args: Vec<String> = Vec::new();
+        let mut push = |flag: &str, value: String| { args.push(flag.to_string()); args.push(value); };
+        if let Some(t) = self.threads { push("-t", t.to_string()); }
+        if let Some(b) = self.batch_size { push("-b", b.to_string()); }
+        if let Some(ub) = self.ubatch_size { push("-ub", ub.to_string()); }
+        if let Some(t) = &self.cache_type_k { push("-ctk", t.clone()); }
+        if let Some(t) = &self.cache_type_v { push("-ctv", t.clone()); }
+        if let Some(np) = self.parallel { push("-np", np.to_string()); }
+        if let Some(fa) = self.flash_attn { push("--flash-attn", if fa { "on" } else { "off" }.to_string()); }
+        if let Some(mmap) = self.mmap { args.push(if mmap { "--mmap" } else { "--no-mmap" }.to_string()); }
+        if self.mlock == Some(true) { args.push("--mlock".to_string()); }
+        args.extend(split_args(self.extra_args.as_deref().unwrap_or(""))?);
+        Ok(args)
+    }
+}
+
+/// Profiles are keyed by model file name, so they survive moving the models directory;
+/// Hugging Face references (`hf:repo[:quant]`) are keyed as given.
+pub fn profile_key(model_path: &str) -> String {
+    if model_path.starts_with("hf:") {
+        return model_path.to_string();
+    }
+    std::path::Path::new(model_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(model_path)
+        .to_string()
+}
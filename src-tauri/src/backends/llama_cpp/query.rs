@@ -1,4 +1,5 @@
 use serde_json::{json, Value};
+use crate::providers::error::ProviderError;
 
 #[tauri::command]
 pub async fn query_llamacpp(
@@ -12,15 +13,24 @@ pub async fn query_llamacpp(
     max_tokens: Option<i32>,
     repeat_last_n: Option<i32>,
     messages: Option<Value>,
-) -> Result<String, String> {
+    request_id: Option<String>,
+    response_schema: Option<Value>,
+    repair_attempts: Option<u32>,
+    project_id: Option<i64>,
+    bypass_cache: Option<bool>,
+    model: Option<String>,
+) -> Result<String, ProviderError> {
     println!("[llama_cpp.query_llamacpp] called with prompt_len={}", prompt.len());
-    // Read selected model from DB settings
-    let model = match crate::db::load_settings(app.clone()) {
-        Ok(s) => {
-            let m = s.model_repo.trim();
-            if m.is_empty() { "local-model".to_string() } else { m.to_string() }
-        }
-        Err(_) => "local-model".to_string(),
+    // An explicit model picks the router's llama-server; otherwise read selected model from DB settings
+    let model = match model.filter(|m| !m.trim().is_empty()) {
+        Some(m) => m.trim().to_string(),
+        None => match crate::db::load_settings(app.clone()) {
+            Ok(s) => {
+                let m = s.model_repo.trim();
+                if m.is_empty() { "local-model".to_string() } else { m.to_string() }
+            }
+            Err(_) => "local-model".to_string(),
+        },
     };
 
     let temp = temperature.unwrap_or(0.8);
@@ -31,24 +41,81 @@ pub async fn query_llamacpp(
     let rep_last_n = repeat_last_n.unwrap_or(64);
 
     // If messages provided, use them; else construct single-turn from prompt
-    let msgs = if let Some(v) = messages { v } else { json!([{ "role": "user", "content": prompt }]) };
+    let mut msgs = if let Some(v) = messages { v } else { json!([{ "role": "user", "content": prompt }]) };
+    // Earlier reasoning is not sent back to the model
+    crate::reasoning::strip_history_json(&mut msgs);
+    // Messages with images become OpenAI-style content parts for multimodal models
+    let has_images = msgs
+        .as_array()
+        .map(|list| list.iter().any(|m| m.get("images").is_some_and(|i| !i.is_null())))
+        .unwrap_or(false);
+    let msgs = if has_images {
+        let mut list: Vec<crate::providers::ChatMessage> = serde_json::from_value(msgs).map_err(|e| e.to_string())?;
+        crate::providers::prepare_images(&mut list).await?;
+        Value::Array(list.iter().map(crate::providers::openai::message_json).collect())
+    } else {
+        msgs
+    };
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post(&format!("http://127.0.0.1:{}/v1/chat/completions", port))
-        .json(&json!({
-            "model": model,
-            "messages": msgs,
-            "temperature": temp,
-            "top_k": tk,
-            "top_p": tp,
-            "min_p": mp,
-            "max_tokens": n_pred,
-            "repeat_last_n": rep_last_n
-        }))
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    let mut body = json!({
+        "model": model,
+        "messages": msgs,
+        "temperature": temp,
+        "top_k": tk,
+        "top_p": tp,
+        "min_p": mp,
+        "max_tokens": n_pred,
+        "repeat_last_n": rep_last_n
+    });
+    // llama-server turns `json_schema` into a sampling grammar
+    if let Some(schema) = &response_schema {
+        body["json_schema"] = schema.clone();
+    }
 
-    response.text().await.map_err(|e| e.to_string())
+    let request_id = crate::streaming::ensure_request_id(request_id);
+    // A freshly started server may still be loading the model
+    super::status::wait_ready(&app, port).await?;
+    let client = crate::providers::http::client(&app)?;
+    let url = format!("http://127.0.0.1:{}/v1/chat/completions", port);
+    let token = super::router::auth_token(&app, port);
+    // Streamed and reassembled: llama-server stops decoding only when a streaming client
+    // disconnects, so `cancel_generation` would otherwise leave it busy
+    let send = |mut body: Value, _attempt: u32| {
+        let url = url.clone();
+        let client = client.clone();
+        let request_id = request_id.clone();
+        let token = token.clone();
+        async move {
+            body["stream"] = json!(true);
+            body["stream_options"] = json!({"include_usage": true});
+            let response = crate::providers::http::send_with_retry(|| {
+                let req = client.post(&url).json(&body);
+                match &token {
+                    Some(t) => req.bearer_auth(t),
+                    None => req,
+                }
+            })
+            .await?;
+
+            let streamed = crate::providers::openai::read_sse_stream(response, &crate::streaming::StreamSink::discard()).await?;
+            Ok(crate::api::streamed_body(streamed, &request_id))
+        }
+    };
+    let cache = crate::cache::CacheSlot::for_body(&app, bypass_cache, &url, &body);
+    if let Some(hit) = cache.as_ref().and_then(|c| c.get(&app)) {
+        return Ok(super::router::with_resident_models(&app, port, hit));
+    }
+    let started = std::time::Instant::now();
+    let raw = match &response_schema {
+        Some(schema) => {
+            let validated = crate::schema::send_validated(body, schema, "/choices/0/message/content", repair_attempts.unwrap_or(0), send);
+            crate::generation::run_cancellable(&app, &request_id, validated).await?
+        }
+        None => crate::generation::run_cancellable(&app, &request_id, send(body, 0)).await?,
+    };
+    crate::api::record_raw_usage(&app, project_id, "local", &model, &raw, started);
+    if let Some(cache) = &cache {
+        cache.put(&app, "local", &model, &raw);
+    }
+    Ok(super::router::with_resident_models(&app, port, raw))
 }
@@ -0,0 +1,574 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::process::Child;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{Emitter, Manager};
+use tokio::sync::oneshot;
+use crate::utils::{get_app_data_dir, get_models_dir};
+use super::server::{build_command, pump_lines, server_alive, terminate};
+use super::status::describe_exit;
+
+const DEFAULT_PORT: u16 = 8090;
+const DEFAULT_MAX_MODELS: usize = 1;
+const DEFAULT_IDLE_TTL_SECS: u64 = 600;
+pub const ROUTER_EVENT: &str = "llamacpp_router_status";
+/// How long a request waits for its model to load.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// How long a request for a new model waits for a resident one to finish its requests
+/// when all slots are taken.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(120);
+const REAP_INTERVAL: Duration = Duration::from_secs(5);
+const LOG_TAIL: usize = 20;
+
+/// A llama-server started by the router for one model.
+struct Instance {
+    model: String,
+    port: u16,
+    pid: u32,
+    pid_file: PathBuf,
+    child: Mutex<Child>,
+    // Requests being proxied right now; busy instances are never unloaded
+    active: AtomicUsize,
+    last_used: Mutex<Instant>,
+    log_tail: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Instance {
+    fn idle_for(&self) -> Duration {
+        self.last_used.lock().map(|t| t.elapsed()).unwrap_or_default()
+    }
+
+    fn exited(&self) -> Option<std::process::ExitStatus> {
+        self.child.lock().ok().and_then(|mut c| c.try_wait().ok().flatten())
+    }
+}
+
+/// Keeps an instance marked busy while a request (including a streamed body) uses it.
+struct Lease(Arc<Instance>);
+
+impl Lease {
+    fn new(instance: &Arc<Instance>) -> Self {
+        instance.active.fetch_add(1, Ordering::SeqCst);
+        Lease(instance.clone())
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if let Ok(mut t) = self.0.last_used.lock() {
+            *t = Instant::now();
+        }
+        self.0.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The running router, its limits and the models it keeps loaded.
+#[derive(Default)]
+pub struct RouterState(pub Mutex<RouterInfo>);
+
+#[derive(Default)]
+pub struct RouterInfo {
+    port: Option<u16>,
+    error: Option<String>,
+    shutdown: Option<oneshot::Sender<()>>,
+    max_models: usize,
+    idle_ttl: Duration,
+    variant: String,
+    instances: Vec<Arc<Instance>>,
+    // Model being loaded right now
+    loading: Option<String>,
+    // Serializes loading and unloading, so concurrent requests do not start a model twice
+    swap: Arc<tokio::sync::Mutex<()>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResidentModel {
+    pub model: String,
+    pub port: u16,
+    pub pid: u32,
+    pub active_requests: usize,
+    pub idle_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouterStatus {
+    pub running: bool,
+    pub port: Option<u16>,
+    pub base_url: Option<String>,
+    pub error: Option<String>,
+    pub max_models: usize,
+    pub idle_ttl_secs: u64,
+    pub loading: Option<String>,
+    pub resident: Vec<ResidentModel>,
+}
+
+fn status(info: &RouterInfo) -> RouterStatus {
+    let running = info.shutdown.is_some();
+    RouterStatus {
+        running,
+        port: info.port,
+        base_url: info.port.filter(|_| running).map(|p| format!("http://127.0.0.1:{}/v1", p)),
+        error: info.error.clone(),
+        max_models: info.max_models,
+        idle_ttl_secs: info.idle_ttl.as_secs(),
+        loading: info.loading.clone(),
+        resident: info
+            .instances
+            .iter()
+            .map(|i| ResidentModel {
+                model: i.model.clone(),
+                port: i.port,
+                pid: i.pid,
+                active_requests: i.active.load(Ordering::SeqCst),
+                idle_secs: i.idle_for().as_secs(),
+            })
+            .collect(),
+    }
+}
+
+fn emit_status(app: &tauri::AppHandle) {
+    let state = app.state::<RouterState>();
+    let current = state.0.lock().map(|info| status(&info));
+    if let Ok(current) = current {
+        let _ = app.emit(ROUTER_EVENT, current);
+    }
+}
+
+/// Pid files of router instances, so servers left by a crashed session can be stopped.
+fn pid_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(get_app_data_dir(app)?.join("runtime/llama-cpp/router"))
+}
+
+/// Pid files left by a previous session that did not shut down cleanly.
+fn orphan_pid_files(app: &tauri::AppHandle) -> Vec<PathBuf> {
+    let Ok(dir) = pid_dir(app) else { return Vec::new(); };
+    let Ok(entries) = std::fs::read_dir(&dir) else { return Vec::new(); };
+    entries.flatten().map(|e| e.path()).collect()
+}
+
+/// Stops the llama-servers recorded in `pid_files`. Blocks for up to a few seconds per process.
+fn stop_orphans(pid_files: Vec<PathBuf>) {
+    for path in pid_files {
+        if let Some(pid) = std::fs::read_to_string(&path).ok().and_then(|s| s.trim().parse::<u32>().ok()) {
+            if server_alive(pid) {
+                println!("[llama_cpp.router] Stopping orphaned llama-server PID {}", pid);
+                terminate(pid, || !server_alive(pid));
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+}
+
+/// Starts, stops or restarts the router to match the `llama_router_*` settings.
+/// Called at startup and after `save_settings`.
+pub fn apply_settings(app: &tauri::AppHandle) -> Result<(), String> {
+    let settings = crate::db::load_stored_settings(app)?;
+    let enabled = settings.llama_router_enabled.unwrap_or(false);
+    let port = settings
+        .llama_router_port
+        .and_then(|p| u16::try_from(p).ok())
+        .filter(|p| *p != 0)
+        .unwrap_or(DEFAULT_PORT);
+    let state = app.state::<RouterState>();
+    let mut info = state.0.lock().map_err(|e| e.to_string())?;
+    info.max_models = settings.llama_router_max_models.and_then(|n| usize::try_from(n).ok()).filter(|n| *n > 0).unwrap_or(DEFAULT_MAX_MODELS);
+    info.idle_ttl = Duration::from_secs(settings.llama_router_idle_ttl_secs.and_then(|s| u64::try_from(s).ok()).unwrap_or(DEFAULT_IDLE_TTL_SECS));
+    info.variant = settings.server_variant.clone().filter(|v| !v.trim().is_empty()).unwrap_or_else(|| "cpu".to_string());
+    if info.shutdown.is_some() && (!enabled || info.port != Some(port)) {
+        if let Some(tx) = info.shutdown.take() {
+            let _ = tx.send(());
+        }
+        println!("[llama_cpp.router] stopped (port {:?})", info.port);
+        info.port = None;
+        if !enabled {
+            let instances = std::mem::take(&mut info.instances);
+            tauri::async_runtime::spawn(async move {
+                for instance in instances {
+                    unload(instance).await;
+                }
+            });
+        }
+    }
+    if !enabled {
+        info.error = None;
+        return Ok(());
+    }
+    if info.shutdown.is_some() {
+        return Ok(());
+    }
+    let orphans = if info.instances.is_empty() { orphan_pid_files(app) } else { Vec::new() };
+    let result = match serve(app.clone(), port) {
+        Ok(tx) => {
+            info.port = Some(port);
+            info.shutdown = Some(tx);
+            info.error = None;
+            Ok(())
+        }
+        Err(e) => {
+            info.error = Some(e.clone());
+            Err(e)
+        }
+    };
+    drop(info);
+    // Stopping a process may take seconds; neither the state lock nor `save_settings` waits for it
+    if !orphans.is_empty() {
+        tauri::async_runtime::spawn_blocking(move || stop_orphans(orphans));
+    }
+    result
+}
+
+/// Bearer token for a request to `port` when the router listens there. The router accepts
+/// the gateway token; plain llama-servers get no token.
+pub fn auth_token(app: &tauri::AppHandle, port: u16) -> Option<String> {
+    router_status_on(app, port)?;
+    crate::gateway::token(app).ok()
+}
+
+fn router_status_on(app: &tauri::AppHandle, port: u16) -> Option<RouterStatus> {
+    let state = app.state::<RouterState>();
+    let info = state.0.lock().ok()?;
+    (info.shutdown.is_some() && info.port == Some(port)).then(|| status(&info))
+}
+
+/// Adds the router's resident models to a `query_llamacpp` reply when `port` is the router.
+pub fn with_resident_models(app: &tauri::AppHandle, port: u16, raw: String) -> String {
+    let Some(current) = router_status_on(app, port) else { return raw; };
+    let Ok(mut v) = serde_json::from_str::<Value>(&raw) else { return raw; };
+    v["router"] = json!({
+        "max_models": current.max_models,
+        "loading": current.loading,
+        "resident": current.resident,
+    });
+    v.to_string()
+}
+
+/// Binds 127.0.0.1:`port` and serves until the returned sender fires. Idle models are
+/// unloaded in the background while the router runs.
+fn serve(app: tauri::AppHandle, port: u16) -> Result<oneshot::Sender<()>, String> {
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| format!("Cannot listen on 127.0.0.1:{}: {}", port, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let router = Router::new()
+        .route("/health", get(|| async { Json(json!({"status": "ok"})) }))
+        .route("/v1/models", get(list_models))
+        .route("/v1/chat/completions", post(proxy))
+        .route("/v1/completions", post(proxy))
+        .route("/v1/embeddings", post(proxy))
+        .with_state(app.clone());
+    let (tx, rx) = oneshot::channel::<()>();
+    let (reaper_tx, mut reaper_rx) = oneshot::channel::<()>();
+    tauri::async_runtime::spawn(async move {
+        let listener = match tokio::net::TcpListener::from_std(listener) {
+            Ok(l) => l,
+            Err(e) => {
+                println!("[llama_cpp.router] listener error: {}", e);
+                return;
+            }
+        };
+        println!("[llama_cpp.router] listening on http://127.0.0.1:{}", port);
+        let shutdown = async {
+            let _ = rx.await;
+            let _ = reaper_tx.send(());
+        };
+        if let Err(e) = axum::serve(listener, router).with_graceful_shutdown(shutdown).await {
+            println!("[llama_cpp.router] server error: {}", e);
+        }
+    });
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = &mut reaper_rx => return,
+                _ = tokio::time::sleep(REAP_INTERVAL) => reap(&app).await,
+            }
+        }
+    });
+    Ok(tx)
+}
+
+/// Unloads models idle for longer than the TTL and forgets instances that exited.
+async fn reap(app: &tauri::AppHandle) {
+    let expired: Vec<Arc<Instance>> = {
+        let state = app.state::<RouterState>();
+        let Ok(mut info) = state.0.lock() else { return; };
+        let ttl = info.idle_ttl;
+        let (expired, kept) = std::mem::take(&mut info.instances).into_iter().partition(|i| {
+            if let Some(exit) = i.exited() {
+                println!("[llama_cpp.router] '{}' exited ({})", i.model, describe_exit(exit));
+                return true;
+            }
+            !ttl.is_zero() && i.active.load(Ordering::SeqCst) == 0 && i.idle_for() > ttl
+        });
+        info.instances = kept;
+        expired
+    };
+    if expired.is_empty() {
+        return;
+    }
+    for instance in expired {
+        println!("[llama_cpp.router] unloading '{}'", instance.model);
+        unload(instance).await;
+    }
+    emit_status(app);
+}
+
+/// Stops an instance's process off the async runtime and removes its pid file.
+async fn unload(instance: Arc<Instance>) {
+    let _ = tokio::task::spawn_blocking(move || {
+        if let Ok(mut child) = instance.child.lock() {
+            if child.try_wait().ok().flatten().is_none() {
+                terminate(instance.pid, || child.try_wait().map(|s| s.is_some()).unwrap_or(true));
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+        let _ = std::fs::remove_file(&instance.pid_file);
+    })
+    .await;
+}
+
+fn free_port() -> Result<u16, String> {
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).map_err(|e| e.to_string())?;
+    listener.local_addr().map(|a| a.port()).map_err(|e| e.to_string())
+}
+
+/// Finds `name` in the models directory: the exact file name, or the name without `.gguf`.
+fn resolve_model(app: &tauri::AppHandle, name: &str) -> Option<(String, PathBuf)> {
+    let dir = get_models_dir(app).ok()?;
+    let name = name.trim();
+    let files = super::models::list_models(app.clone()).ok()?;
+    files
+        .into_iter()
+        .filter(|f| f.to_lowercase().ends_with(".gguf"))
+        .find(|f| f.eq_ignore_ascii_case(name) || f[..f.len() - 5].eq_ignore_ascii_case(name))
+        .map(|f| {
+            let path = dir.join(&f);
+            (f, path)
+        })
+}
+
+/// A lease on the resident instance of `model`, if it is loaded.
+fn lease_resident(app: &tauri::AppHandle, model: &str) -> Option<Lease> {
+    let state = app.state::<RouterState>();
+    let info = state.0.lock().ok()?;
+    info.instances
+        .iter()
+        .find(|i| i.model == model && i.exited().is_none())
+        .map(Lease::new)
+}
+
+/// Returns a lease on a loaded instance of `model`, loading it (and unloading the least
+/// recently used idle model when all slots are taken) if needed.
+async fn acquire(app: &tauri::AppHandle, model: &str, path: PathBuf) -> Result<Lease, Response> {
+    if let Some(lease) = lease_resident(app, model) {
+        return Ok(lease);
+    }
+    let swap = app.state::<RouterState>().0.lock().map(|i| i.swap.clone()).map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let _swap = swap.lock().await;
+    // Another request may have loaded it while this one waited
+    if let Some(lease) = lease_resident(app, model) {
+        return Ok(lease);
+    }
+
+    let started = Instant::now();
+    loop {
+        let victim = {
+            let state = app.state::<RouterState>();
+            let mut info = state.0.lock().map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            info.instances.retain(|i| i.exited().is_none());
+            if info.instances.len() < info.max_models {
+                break;
+            }
+            let lru = info
+                .instances
+                .iter()
+                .enumerate()
+                .filter(|(_, i)| i.active.load(Ordering::SeqCst) == 0)
+                .max_by_key(|(_, i)| i.idle_for())
+                .map(|(idx, _)| idx);
+            lru.map(|idx| info.instances.remove(idx))
+        };
+        match victim {
+            Some(instance) => {
+                println!("[llama_cpp.router] swapping out '{}' for '{}'", instance.model, model);
+                unload(instance).await;
+                emit_status(app);
+            }
+            None if started.elapsed() > BUSY_TIMEOUT => {
+                return Err(error(StatusCode::SERVICE_UNAVAILABLE, "All resident models are busy; try again later or raise llama_router_max_models"));
+            }
+            None => tokio::time::sleep(Duration::from_millis(250)).await,
+        }
+    }
+
+    set_loading(app, Some(model.to_string()));
+    let loaded = load(app, model, path).await;
+    set_loading(app, None);
+    let instance = loaded.map_err(|e| error(StatusCode::SERVICE_UNAVAILABLE, e))?;
+    let lease = Lease::new(&instance);
+    if let Ok(mut info) = app.state::<RouterState>().0.lock() {
+        info.instances.push(instance);
+    }
+    emit_status(app);
+    Ok(lease)
+}
+
+fn set_loading(app: &tauri::AppHandle, model: Option<String>) {
+    if let Ok(mut info) = app.state::<RouterState>().0.lock() {
+        info.loading = model;
+    }
+    emit_status(app);
+}
+
+/// Starts llama-server for `model` on a free port and waits until `/health` reports it ready.
+async fn load(app: &tauri::AppHandle, model: &str, path: PathBuf) -> Result<Arc<Instance>, String> {
+    let variant = app.state::<RouterState>().0.lock().map(|i| i.variant.clone()).map_err(|e| e.to_string())?;
+    let port = free_port()?;
+    println!("[llama_cpp.router] loading '{}' on port {} (variant '{}')", model, port, variant);
+    let mut cmd = build_command(app, &path.to_string_lossy(), &variant, port, None)?;
+    let mut child = cmd.spawn().map_err(|e| format!("Ошибка запуска сервера: {}", e))?;
+    let pid = child.id();
+    let pid_file = pid_dir(app)?.join(format!("{}.pid", pid));
+    if let Some(parent) = pid_file.parent() { let _ = std::fs::create_dir_all(parent); }
+    let _ = std::fs::write(&pid_file, pid.to_string());
+
+    let log_tail: Arc<Mutex<VecDeque<String>>> = Arc::default();
+    let on_line = |app: tauri::AppHandle, tail: Arc<Mutex<VecDeque<String>>>, model: String| move |line: String| {
+        let _ = app.emit("llamacpp_server_log", json!({"line": line, "model": model}));
+        if let Ok(mut tail) = tail.lock() {
+            if tail.len() == LOG_TAIL { tail.pop_front(); }
+            tail.push_back(line);
+        }
+    };
+    if let Some(stdout) = child.stdout.take() {
+        pump_lines(stdout, on_line(app.clone(), log_tail.clone(), model.to_string()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        pump_lines(stderr, on_line(app.clone(), log_tail.clone(), model.to_string()));
+    }
+    let instance = Arc::new(Instance {
+        model: model.to_string(),
+        port,
+        pid,
+        pid_file,
+        child: Mutex::new(child),
+        active: AtomicUsize::new(0),
+        last_used: Mutex::new(Instant::now()),
+        log_tail,
+    });
+
+    let client = crate::providers::http::client(app)?;
+    let url = format!("http://127.0.0.1:{}/health", port);
+    let started = Instant::now();
+    loop {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        if let Some(exit) = instance.exited() {
+            let _ = std::fs::remove_file(&instance.pid_file);
+            let last = instance.log_tail.lock().ok().and_then(|t| t.back().cloned()).unwrap_or_default();
+            return Err(format!("llama-server for '{}' exited while loading ({}): {}", model, describe_exit(exit), last));
+        }
+        if started.elapsed() > LOAD_TIMEOUT {
+            unload(instance).await;
+            return Err(format!("'{}' did not load within {} min", model, LOAD_TIMEOUT.as_secs() / 60));
+        }
+        if let Ok(response) = client.get(&url).timeout(Duration::from_secs(2)).send().await {
+            if response.status().is_success() {
+                println!("[llama_cpp.router] '{}' ready after {} ms", model, started.elapsed().as_millis());
+                return Ok(instance);
+            }
+        }
+    }
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({"error": {"message": message.into(), "type": "router_error"}}))).into_response()
+}
+
+async fn list_models(State(app): State<tauri::AppHandle>, headers: HeaderMap) -> Response {
+    if let Err(denied) = crate::gateway::authorize(&app, &headers) {
+        return denied;
+    }
+    let files = match super::models::list_models(app.clone()) {
+        Ok(files) => files,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    let loaded: Vec<String> = app
+        .state::<RouterState>()
+        .0
+        .lock()
+        .map(|info| info.instances.iter().map(|i| i.model.clone()).collect())
+        .unwrap_or_default();
+    let data: Vec<Value> = files
+        .into_iter()
+        .filter(|f| f.to_lowercase().ends_with(".gguf"))
+        .map(|f| {
+            let state = if loaded.contains(&f) { "loaded" } else { "unloaded" };
+            json!({"id": f, "object": "model", "owned_by": "llama.cpp", "status": state})
+        })
+        .collect();
+    Json(json!({"object": "list", "data": data})).into_response()
+}
+
+/// Forwards an OpenAI request to the llama-server of its `model`, streaming the answer back.
+async fn proxy(State(app): State<tauri::AppHandle>, headers: HeaderMap, uri: Uri, body: Bytes) -> Response {
+    if let Err(denied) = crate::gateway::authorize(&app, &headers) {
+        return denied;
+    }
+    let model = match serde_json::from_slice::<Value>(&body) {
+        Ok(v) => v.get("model").and_then(|m| m.as_str()).unwrap_or("").to_string(),
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid JSON body: {}", e)),
+    };
+    if model.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "`model` must name a model file from the models directory");
+    }
+    let Some((model, path)) = resolve_model(&app, &model) else {
+        return error(StatusCode::NOT_FOUND, format!("Model '{}' is not in the models directory", model));
+    };
+    let lease = match acquire(&app, &model, path).await {
+        Ok(lease) => lease,
+        Err(response) => return response,
+    };
+    let client = match crate::providers::http::client(&app) {
+        Ok(c) => c,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    let url = format!("http://127.0.0.1:{}{}", lease.0.port, uri.path());
+    let upstream = match client.post(&url).header(header::CONTENT_TYPE, "application/json").body(body).send().await {
+        Ok(r) => r,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, format!("llama-server for '{}' did not answer: {}", model, e)),
+    };
+    let status = StatusCode::from_u16(upstream.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let content_type = upstream
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/json")
+        .to_string();
+    // The lease lives as long as the body stream, so a streaming answer keeps the model busy
+    let stream = upstream.bytes_stream().map(move |chunk| {
+        let _busy = &lease;
+        chunk
+    });
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from_stream(stream))
+        .unwrap_or_else(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[tauri::command]
+pub fn llamacpp_router_status(app: tauri::AppHandle) -> Result<RouterStatus, String> {
+    let state = app.state::<RouterState>();
+    let info = state.0.lock().map_err(|e| e.to_string())?;
+    Ok(status(&info))
+}
@@ -1,18 +1,158 @@
-use std::process::Command;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use std::fs;
+use sysinfo::{Pid, ProcessStatus, System};
 use tauri::{Manager, Emitter};
-use crate::utils::{get_runtime_dir, find_first_with_names};
+use crate::utils::{get_app_data_dir, get_runtime_dir, find_first_with_names, llama_server_binary};
+use super::launch::DEFAULT_CTX_SIZE;
+use super::status::{self, ServerPhase, ServerStatus};
+use super::watchdog::{self, CrashHistory};
 
-#[tauri::command]
-pub fn start_llamacpp_server(app: tauri::AppHandle, model_path: String, variant: String, port: u16) -> Result<(), String> {
-    println!("[llama_cpp.start_server] Starting with model_path='{}', variant='{}'", model_path, variant);
+/// How long llama-server gets to exit after SIGTERM before it is killed.
+const STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// The llama-server process started by this app instance and what is known about it.
+#[derive(Default)]
+pub struct ServerInfo {
+    pub child: Option<Child>,
+    pub status: ServerStatus,
+    /// Last error line from the log, used as the failure reason.
+    pub last_error: Option<String>,
+    /// Bumped on every start and stop, so log readers and pollers of an old process go quiet.
+    pub generation: u64,
+    /// What is running, so the watchdog can start it again.
+    pub spec: Option<LaunchSpec>,
+    /// Last lines of output, reported when the server crashes.
+    pub log_tail: VecDeque<String>,
+    /// Context size llama-server reported (`n_ctx = ...`).
+    pub n_ctx: Option<u32>,
+    /// Recent crashes, for the watchdog's backoff and crash-loop limit.
+    pub crashes: CrashHistory,
+}
+
+/// Arguments of `start_llamacpp_server`, kept for watchdog restarts.
+#[derive(Debug, Clone)]
+pub struct LaunchSpec {
+    pub model_path: String,
+    pub variant: String,
+    pub port: u16,
+    /// Overrides the profile's context size after out-of-memory crashes.
+    pub ctx_size: Option<u32>,
+}
+
+#[derive(Default)]
+pub struct LlamaServerState(pub Mutex<ServerInfo>);
+
+pub(super) fn pid_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(get_app_data_dir(app)?.join("runtime/llama-cpp/llama-server.pid"))
+}
+
+/// Whether `pid` is a live llama-server. A pid file left by a crashed session may point
+/// to a process that is gone or to an unrelated process that reused the id.
+pub(super) fn server_alive(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut sys = System::new();
+    if !sys.refresh_process(pid) {
+        return false;
+    }
+    sys.process(pid)
+        .map(|p| p.status() != ProcessStatus::Zombie && p.name().to_lowercase().starts_with("llama-server"))
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn signal(pid: u32, sig: libc::c_int) {
+    // SAFETY: kill(2) takes plain integers and has no memory effects
+    unsafe { libc::kill(pid as libc::pid_t, sig); }
+}
+
+/// Stops `pid` and waits until `exited` reports it gone. On Unix the server gets SIGTERM
+/// and `STOP_GRACE` to shut down cleanly, then SIGKILL; on Windows the process tree is
+/// killed with `taskkill`.
+pub(super) fn terminate(pid: u32, mut exited: impl FnMut() -> bool) {
+    #[cfg(unix)]
+    {
+        signal(pid, libc::SIGTERM);
+        let deadline = Instant::now() + STOP_GRACE;
+        while Instant::now() < deadline {
+            if exited() { return; }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        println!("[llama_cpp.stop_server] PID {} still running after SIGTERM, sending SIGKILL", pid);
+        signal(pid, libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    {
+        match Command::new("taskkill").args(["/PID", &pid.to_string(), "/T", "/F"]).status() {
+            Ok(status) if !status.success() => println!("[llama_cpp.stop_server] taskkill exited with status {:?}", status.code()),
+            Err(e) => println!("[llama_cpp.stop_server] taskkill failed: {}", e),
+            _ => {}
+        }
+    }
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while !exited() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Stops the server held in `LlamaServerState`, or else the one recorded in the pid file
+/// (left by a previous session), and removes the pid file.
+fn stop_server(app: &tauri::AppHandle) -> Result<(), String> {
+    let pid_file = pid_file(app)?;
+    let (child, was_running) = {
+        let state = app.state::<LlamaServerState>();
+        let mut info = state.0.lock().map_err(|e| e.to_string())?;
+        info.generation += 1;
+        let was_running = info.status.state != ServerPhase::Stopped;
+        info.status = ServerStatus::default();
+        info.last_error = None;
+        info.spec = None;
+        info.log_tail.clear();
+        info.n_ctx = None;
+        info.crashes = CrashHistory::default();
+        (info.child.take(), was_running)
+    };
+    if was_running {
+        status::emit(app, &ServerStatus::default());
+    }
+    if let Some(mut child) = child {
+        let pid = child.id();
+        println!("[llama_cpp.stop_server] Stopping PID {}", pid);
+        terminate(pid, || child.try_wait().map(|s| s.is_some()).unwrap_or(true));
+        if child.try_wait().ok().flatten().is_none() {
+            // Windows/SIGKILL fallback when the wait above timed out; reaps the process on Unix
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    } else if let Some(pid) = fs::read_to_string(&pid_file).ok().and_then(|s| s.trim().parse::<u32>().ok()) {
+        if server_alive(pid) {
+            println!("[llama_cpp.stop_server] Stopping PID {} from pid file", pid);
+            terminate(pid, || !server_alive(pid));
+        } else {
+            println!("[llama_cpp.stop_server] Stale pid file (PID {} is not running)", pid);
+        }
+    }
+    if pid_file.exists() {
+        let _ = fs::remove_file(&pid_file);
+    }
+    Ok(())
+}
+
+/// A llama-server command for `model_path` (a GGUF path or `hf:` reference) listening on
+/// 127.0.0.1:`port`, with the model's launch profile applied and output piped.
+/// `ctx_override` replaces the profile's context size.
+pub(super) fn build_command(app: &tauri::AppHandle, model_path: &str, variant: &str, port: u16, ctx_override: Option<u32>) -> Result<Command, String> {
     // Resolve base runtime dir for selected variant
-    let base_dir = get_runtime_dir(&app, "llama-cpp", &variant)?;
+    let base_dir = get_runtime_dir(app, "llama-cpp", variant)?;
     println!("[llama_cpp.start_server] base_dir='{}'", base_dir.display());
 
-    // Find llama-server.exe under the variant directory
-    let server_path = find_first_with_names(&base_dir.as_path(), &["llama-server.exe"], 8)
-        .ok_or_else(|| format!("Не найден llama-server.exe в {}", base_dir.display()))?;
+    // Find llama-server under the variant directory
+    let binary = llama_server_binary();
+    let server_path = find_first_with_names(&base_dir.as_path(), &[binary], 8)
+        .ok_or_else(|| format!("Не найден {} в {}", binary, base_dir.display()))?;
     println!("[llama_cpp.start_server] server_path='{}'", server_path.display());
 
     // Guard: prevent launching ARM binaries on non-ARM hosts
@@ -24,184 +164,166 @@ pub fn start_llamacpp_server(app: tauri::AppHandle, model_path: String, variant:
         ));
     }
 
-    // Choose -ngl depending on selected variant: CPU builds must use 0 to avoid GPU offload
-    let ngl_val: &str = match variant.as_str() {
-        "cpu" | "cpu_arm" => "0",
-        _ => "99",
+    // Saved per-model options; CPU builds default to -ngl 0 to avoid GPU offload
+    let profile = crate::db::load_launch_profile(app, model_path)?.unwrap_or_default();
+    profile.validate()?;
+    let cpu_only = matches!(variant, "cpu" | "cpu_arm");
+    let ctx_size = ctx_override.or(profile.ctx_size).unwrap_or(DEFAULT_CTX_SIZE);
+    let gpu_layers = match profile.gpu_layers {
+        Some(n) if cpu_only && n > 0 => {
+            return Err(format!("Профиль запуска задаёт {} GPU-слоёв, но выбран CPU-вариант '{}'", n, variant));
+        }
+        Some(n) => n,
+        None if cpu_only => 0,
+        None => 99,
     };
 
     // Detect HF reference: we use a special prefix "hf:" coming from the frontend
     let is_hf = model_path.starts_with("hf:");
 
     use std::process::Stdio;
-    let mut cmd = Command::new(server_path);
+    let mut cmd = Command::new(&server_path);
     cmd.current_dir(&base_dir);
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
 
     if is_hf {
         let hf_ref = model_path.trim_start_matches("hf:");
-        println!("[llama_cpp.start_server] Launching llama-server with -hf='{}' -ngl={} on port {}", hf_ref, ngl_val, port);
-        cmd.args(&[
-            "-hf",
-            hf_ref,
-            "-c",
-            "2048",
-            "-ngl",
-            ngl_val,
-            "--host",
-            "127.0.0.1",
-            "--port",
-            &port.to_string(),
-        ]);
+        println!("[llama_cpp.start_server] Launching llama-server with -hf='{}' on port {}", hf_ref, port);
+        cmd.args(["-hf", hf_ref]);
     } else {
         // Validate local model path exists when using -m
-        if !std::path::Path::new(&model_path).exists() {
+        if !std::path::Path::new(model_path).exists() {
             return Err(format!("Файл модели не найден: {}", model_path));
         }
-        println!("[llama_cpp.start_server] Launching llama-server with -m on port {} (-ngl={})", port, ngl_val);
-        cmd.args(&[
-            "-m",
-            &model_path,
-            "-c",
-            "2048",
-            "-ngl",
-            ngl_val,
-            "--host",
-            "127.0.0.1",
-            "--port",
-            &port.to_string(),
-        ]);
+        println!("[llama_cpp.start_server] Launching llama-server with -m on port {}", port);
+        cmd.args(["-m", model_path]);
     }
+    let extra = profile.args()?;
+    println!("[llama_cpp.start_server] -c {} -ngl {} {}", ctx_size, gpu_layers, extra.join(" "));
+    cmd.args(["-c", &ctx_size.to_string(), "-ngl", &gpu_layers.to_string()]);
+    cmd.args(&extra);
+    cmd.args(["--host", "127.0.0.1", "--port", &port.to_string()]);
 
-    let mut child = cmd.spawn().map_err(|e| format!("Ошибка запуска сервера: {}", e))?;
+    // Binaries installed before the executable bit was set on extraction
+    crate::utils::set_executable(&server_path)?;
+    Ok(cmd)
+}
+
+// Runs off the main thread: stopping a previous server may wait for it to exit
+#[tauri::command(async)]
+pub fn start_llamacpp_server(app: tauri::AppHandle, model_path: String, variant: String, port: u16) -> Result<(), String> {
+    println!("[llama_cpp.start_server] Starting with model_path='{}', variant='{}'", model_path, variant);
+    let spec = LaunchSpec { model_path, variant, port, ctx_size: None };
+    let cmd = build_command(&app, &spec.model_path, &spec.variant, port, None)?;
 
-    // Save PID to a global pid file to enable stopping later
-    if let Ok(app_dir) = app.path().app_data_dir() {
-        let pid_file = app_dir.join("runtime/llama-cpp/llama-server.pid");
+    // Only one managed server listens at a time; replace the running one (or an orphan from a previous session)
+    stop_server(&app)?;
+    spawn_server(&app, spec, cmd, None)
+}
+
+/// Spawns `cmd` as the managed server and starts its log readers, readiness poll and
+/// watchdog. A watchdog restart passes the `generation` it replaces and is dropped if
+/// the server was stopped or started again meanwhile.
+pub(super) fn spawn_server(app: &tauri::AppHandle, spec: LaunchSpec, mut cmd: Command, replaces: Option<u64>) -> Result<(), String> {
+    let port = spec.port;
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let reason = format!("Ошибка запуска сервера: {}", e);
+            status::emit(app, &ServerStatus { state: ServerPhase::Failed, reason: Some(reason.clone()), port: Some(port), ..Default::default() });
+            return Err(reason);
+        }
+    };
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let pid = child.id();
+    let generation = {
+        let state = app.state::<LlamaServerState>();
+        let mut info = state.0.lock().map_err(|e| e.to_string())?;
+        if replaces.is_some_and(|g| g != info.generation) {
+            drop(info);
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(());
+        }
+        info.generation += 1;
+        info.status = ServerStatus {
+            state: ServerPhase::Starting,
+            model: Some(spec.model_path.clone()),
+            port: Some(port),
+            pid: Some(pid),
+            ..Default::default()
+        };
+        info.last_error = None;
+        info.log_tail.clear();
+        info.n_ctx = None;
+        info.child = Some(child);
+        info.spec = Some(spec);
+        status::emit(app, &info.status);
+        info.generation
+    };
+
+    // Save PID to a global pid file so a server orphaned by a crash can be stopped later
+    if let Ok(pid_file) = pid_file(app) {
         if let Some(parent) = pid_file.parent() { let _ = fs::create_dir_all(parent); }
-        let _ = std::fs::write(&pid_file, format!("{}", child.id()));
+        let _ = std::fs::write(&pid_file, format!("{}", pid));
         println!("[llama_cpp.start_server] wrote pid file: {}", pid_file.display());
     }
 
     // Stream logs to frontend via events
-    let app_handle = app.clone();
-    if let Some(stdout) = child.stdout.take() {
-        std::thread::spawn(move || {
-            use std::io::Read;
-            let mut reader = stdout;
-            let mut buf = [0u8; 4096];
-            let mut line_buf: Vec<u8> = Vec::with_capacity(8192);
-            loop {
-                match reader.read(&mut buf) {
-                    Ok(0) => { // EOF; flush remaining
-                        if !line_buf.is_empty() {
-                            if let Ok(s) = String::from_utf8(line_buf.clone()) {
-                                let _ = app_handle.emit("llamacpp_server_log", serde_json::json!({"line": s}));
-                            }
-                            line_buf.clear();
-                        }
-                        break;
-                    }
-                    Ok(n) => {
-                        for &b in &buf[..n] {
-                            if b == b'\n' || b == b'\r' {
-                                if !line_buf.is_empty() {
-                                    if let Ok(s) = String::from_utf8(line_buf.clone()) {
-                                        let _ = app_handle.emit("llamacpp_server_log", serde_json::json!({"line": s}));
-                                    }
-                                    line_buf.clear();
-                                }
-                            } else {
-                                line_buf.push(b);
-                                // Avoid unbounded growth if no delimiters appear
-                                if line_buf.len() > 32 * 1024 {
-                                    if let Ok(s) = String::from_utf8(line_buf.clone()) {
-                                        let _ = app_handle.emit("llamacpp_server_log", serde_json::json!({"line": s}));
-                                    }
-                                    line_buf.clear();
-                                }
-                            }
-                        }
-                    }
-                    Err(_) => break,
-                }
-            }
-        });
+    let on_line = |app: tauri::AppHandle| move |line: String| {
+        let _ = app.emit("llamacpp_server_log", serde_json::json!({"line": line}));
+        status::on_log_line(&app, generation, &line);
+    };
+    if let Some(stdout) = stdout {
+        pump_lines(stdout, on_line(app.clone()));
     }
-    let app_handle_err = app.clone();
-    if let Some(stderr) = child.stderr.take() {
-        std::thread::spawn(move || {
-            use std::io::Read;
-            let mut reader = stderr;
-            let mut buf = [0u8; 4096];
-            let mut line_buf: Vec<u8> = Vec::with_capacity(8192);
-            loop {
-                match reader.read(&mut buf) {
-                    Ok(0) => {
-                        if !line_buf.is_empty() {
-                            if let Ok(s) = String::from_utf8(line_buf.clone()) {
-                                let _ = app_handle_err.emit("llamacpp_server_log", serde_json::json!({"line": s}));
-                            }
-                            line_buf.clear();
-                        }
-                        break;
-                    }
-                    Ok(n) => {
-                        for &b in &buf[..n] {
-                            if b == b'\n' || b == b'\r' {
-                                if !line_buf.is_empty() {
-                                    if let Ok(s) = String::from_utf8(line_buf.clone()) {
-                                        let _ = app_handle_err.emit("llamacpp_server_log", serde_json::json!({"line": s}));
-                                    }
-                                    line_buf.clear();
-                                }
-                            } else {
-                                line_buf.push(b);
-                                if line_buf.len() > 32 * 1024 {
-                                    if let Ok(s) = String::from_utf8(line_buf.clone()) {
-                                        let _ = app_handle_err.emit("llamacpp_server_log", serde_json::json!({"line": s}));
-                                    }
-                                    line_buf.clear();
-                                }
+    if let Some(stderr) = stderr {
+        pump_lines(stderr, on_line(app.clone()));
+    }
+    status::watch_readiness(app.clone(), generation, port);
+    watchdog::supervise(app.clone(), generation);
+    Ok(())
+}
+
+/// Reads server output on a thread and hands it to `on_line` line by line.
+pub(super) fn pump_lines(mut reader: impl std::io::Read + Send + 'static, mut on_line: impl FnMut(String) + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        let mut line_buf: Vec<u8> = Vec::with_capacity(8192);
+        let mut flush = |line_buf: &mut Vec<u8>| {
+            if !line_buf.is_empty() {
+                on_line(String::from_utf8_lossy(line_buf).into_owned());
+                line_buf.clear();
+            }
+        };
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => { // EOF; flush remaining
+                    flush(&mut line_buf);
+                    break;
+                }
+                Ok(n) => {
+                    for &b in &buf[..n] {
+                        if b == b'\n' || b == b'\r' {
+                            flush(&mut line_buf);
+                        } else {
+                            line_buf.push(b);
+                            // Avoid unbounded growth if no delimiters appear
+                            if line_buf.len() > 32 * 1024 {
+                                flush(&mut line_buf);
                             }
                         }
                     }
-                    Err(_) => break,
                 }
             }
-        });
-    }
-
-    Ok(())
+        }
+    });
 }
 
-#[tauri::command]
+#[tauri::command(async)]
 pub fn stop_llamacpp_server(app: tauri::AppHandle) -> Result<(), String> {
-    // Read pid file and attempt to kill the process (Windows)
-    let app_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Не удалось получить app data dir: {}", e))?;
-    let pid_file = app_dir.join("runtime/llama-cpp/llama-server.pid");
-    if !pid_file.exists() {
-        return Ok(()); // nothing to stop
-    }
-    let pid_str = std::fs::read_to_string(&pid_file).map_err(|e| e.to_string())?;
-    let pid_str = pid_str.trim();
-    if pid_str.is_empty() {
-        let _ = std::fs::remove_file(&pid_file);
-        return Ok(());
-    }
-    println!("[llama_cpp.stop_server] Stopping PID {}", pid_str);
-    // Use taskkill to kill the process tree
-    let status = Command::new("taskkill")
-        .args(["/PID", pid_str, "/T", "/F"])
-        .status()
-        .map_err(|e| format!("Ошибка taskkill: {}", e))?;
-    if !status.success() {
-        println!("[llama_cpp.stop_server] taskkill exited with status {:?}", status.code());
-    }
-    let _ = std::fs::remove_file(&pid_file);
-    Ok(())
-}
\ No newline at end of file
+    stop_server(&app)
+}
@@ -0,0 +1,223 @@
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+use super::server::{LlamaServerState, ServerInfo};
+
+pub const STATUS_EVENT: &str = "llamacpp_server_status";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Loading from `-hf` includes the download, so this is generous; the poller gives up
+/// earlier when the process exits.
+const READY_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerPhase {
+    #[default]
+    Stopped,
+    /// Process spawned, HTTP endpoint not answering yet.
+    Starting,
+    /// `/health` answers 503 or the log shows the model being read.
+    Loading,
+    Ready,
+    Failed,
+    /// Crashed; the watchdog starts it again after a backoff.
+    Restarting,
+}
+
+/// Payload of the `llamacpp_server_status` event and command.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ServerStatus {
+    pub state: ServerPhase,
+    /// 0..1 while loading, when llama-server reports a percentage.
+    pub progress: Option<f32>,
+    /// Why the server failed.
+    pub reason: Option<String>,
+    pub model: Option<String>,
+    pub port: Option<u16>,
+    pub pid: Option<u32>,
+}
+
+impl ServerStatus {
+    pub fn is_pending(&self) -> bool {
+        matches!(self.state, ServerPhase::Starting | ServerPhase::Loading | ServerPhase::Restarting)
+    }
+}
+
+pub(super) fn emit(app: &tauri::AppHandle, status: &ServerStatus) {
+    let _ = app.emit(STATUS_EVENT, status);
+}
+
+/// Applies `f` to the server started as `generation`; does nothing once it has been
+/// stopped or replaced. Emits the status when `f` reports a change.
+fn update(app: &tauri::AppHandle, generation: u64, f: impl FnOnce(&mut ServerInfo) -> bool) {
+    let changed = {
+        let state = app.state::<LlamaServerState>();
+        let Ok(mut info) = state.0.lock() else { return; };
+        if info.generation != generation || !f(&mut info) {
+            return;
+        }
+        info.status.clone()
+    };
+    println!("[llama_cpp.status] {:?} progress={:?} reason={:?}", changed.state, changed.progress, changed.reason);
+    emit(app, &changed);
+}
+
+/// Reads a trailing `NN%` / `NN.N%` (download and loading progress bars).
+fn parse_progress(line: &str) -> Option<f32> {
+    let end = line.rfind('%')?;
+    let head = &line[..end];
+    let start = head.rfind(|c: char| !(c.is_ascii_digit() || c == '.')).map(|i| i + 1).unwrap_or(0);
+    head[start..].parse::<f32>().ok().filter(|p| (0.0..=100.0).contains(p)).map(|p| p / 100.0)
+}
+
+/// Lines of output kept for crash reports.
+const LOG_TAIL: usize = 30;
+
+/// Reads the context size from `llama_context: n_ctx = 4096` (not `n_ctx_train`, `n_ctx_per_seq`).
+fn parse_n_ctx(line: &str) -> Option<u32> {
+    let rest = &line[line.find("n_ctx")? + "n_ctx".len()..];
+    if !rest.starts_with([' ', '=']) {
+        return None;
+    }
+    rest.trim_start_matches([' ', '=']).split_whitespace().next()?.parse().ok()
+}
+
+fn is_error_line(lower: &str) -> bool {
+    lower.contains("error") || lower.contains("failed") || lower.contains("couldn't") || lower.contains("unable to")
+}
+
+/// Tracks loading progress from one line of llama-server output.
+pub(super) fn on_log_line(app: &tauri::AppHandle, generation: u64, line: &str) {
+    let lower = line.to_lowercase();
+    update(app, generation, |info| {
+        if is_error_line(&lower) {
+            info.last_error = Some(line.trim().to_string());
+        }
+        if info.log_tail.len() == LOG_TAIL {
+            info.log_tail.pop_front();
+        }
+        info.log_tail.push_back(line.to_string());
+        if let Some(n_ctx) = parse_n_ctx(line) {
+            info.n_ctx = Some(n_ctx);
+        }
+        if !matches!(info.status.state, ServerPhase::Starting | ServerPhase::Loading) {
+            return false;
+        }
+        if lower.contains("model loaded") {
+            info.status.state = ServerPhase::Ready;
+            info.status.progress = Some(1.0);
+            return true;
+        }
+        if let Some(progress) = parse_progress(line) {
+            info.status.state = ServerPhase::Loading;
+            info.status.progress = Some(progress);
+            return true;
+        }
+        if info.status.state == ServerPhase::Starting && (lower.contains("loading model") || lower.contains("load_tensors") || lower.contains("llama_model_load")) {
+            info.status.state = ServerPhase::Loading;
+            return true;
+        }
+        false
+    });
+}
+
+pub(super) fn describe_exit(status: std::process::ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(sig) = status.signal() {
+            return format!("killed by signal {}", sig);
+        }
+    }
+    match status.code() {
+        Some(code) => format!("exit code {}", code),
+        None => "unknown exit status".to_string(),
+    }
+}
+
+/// Marks the server failed. `reason` defaults to the last error line it logged.
+fn fail(app: &tauri::AppHandle, generation: u64, reason: String) {
+    update(app, generation, |info| {
+        let detail = info.last_error.clone().map(|e| format!(": {}", e)).unwrap_or_default();
+        info.status.state = ServerPhase::Failed;
+        info.status.reason = Some(format!("{}{}", reason, detail));
+        true
+    });
+}
+
+/// Polls `/health` until the server started as `generation` is ready, exits or times out.
+/// An exit is left to the watchdog to report.
+pub(super) fn watch_readiness(app: tauri::AppHandle, generation: u64, port: u16) {
+    tauri::async_runtime::spawn(async move {
+        let client = match crate::providers::http::client(&app) {
+            Ok(c) => c,
+            Err(e) => return fail(&app, generation, e),
+        };
+        let url = format!("http://127.0.0.1:{}/health", port);
+        let started = Instant::now();
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            // Stop when the server was replaced, stopped or became ready from the log
+            {
+                let state = app.state::<LlamaServerState>();
+                let Ok(mut info) = state.0.lock() else { return; };
+                if info.generation != generation || !info.status.is_pending() {
+                    return;
+                }
+                if !info.child.as_mut().is_some_and(|c| matches!(c.try_wait(), Ok(None))) {
+                    return;
+                }
+            }
+            if started.elapsed() > READY_TIMEOUT {
+                return fail(&app, generation, format!("llama-server did not become ready within {} min", READY_TIMEOUT.as_secs() / 60));
+            }
+            // Connection errors mean the HTTP listener is not up yet
+            let Ok(response) = client.get(&url).timeout(Duration::from_secs(2)).send().await else { continue; };
+            match response.status().as_u16() {
+                200 => update(&app, generation, |info| {
+                    info.status.state = ServerPhase::Ready;
+                    info.status.progress = Some(1.0);
+                    true
+                }),
+                503 => update(&app, generation, |info| {
+                    let changed = info.status.state != ServerPhase::Loading;
+                    info.status.state = ServerPhase::Loading;
+                    changed
+                }),
+                _ => {}
+            }
+        }
+    });
+}
+
+/// Waits while the managed server on `port` is still starting, so the first request after
+/// `start_llamacpp_server` does not hit a closed port. Other ports are not waited on.
+pub async fn wait_ready(app: &tauri::AppHandle, port: u16) -> Result<(), String> {
+    let started = Instant::now();
+    loop {
+        let status = current(app)?;
+        if status.port != Some(port) {
+            return Ok(());
+        }
+        match status.state {
+            ServerPhase::Failed => {
+                return Err(format!("llama-server failed to start: {}", status.reason.unwrap_or_default()));
+            }
+            _ if !status.is_pending() => return Ok(()),
+            _ if started.elapsed() > READY_TIMEOUT => return Err("llama-server is still loading the model".to_string()),
+            _ => tokio::time::sleep(Duration::from_millis(250)).await,
+        }
+    }
+}
+
+fn current(app: &tauri::AppHandle) -> Result<ServerStatus, String> {
+    let state = app.state::<LlamaServerState>();
+    let info = state.0.lock().map_err(|e| e.to_string())?;
+    Ok(info.status.clone())
+}
+
+#[tauri::command]
+pub fn llamacpp_server_status(app: tauri::AppHandle) -> Result<ServerStatus, String> {
+    current(&app)
+}
@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+use super::server::{build_command, pid_file, spawn_server, LlamaServerState};
+use super::status::{self, describe_exit, ServerPhase};
+
+pub const CRASH_EVENT: &str = "llamacpp_server_crashed";
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// Crashes older than this no longer count towards the crash-loop limit.
+const CRASH_WINDOW: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_MAX_RESTARTS: u32 = 5;
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Consecutive out-of-memory crashes before the context size is halved.
+const OOM_FALLBACK_AFTER: u32 = 2;
+const MIN_CTX_SIZE: u32 = 512;
+/// Starting point for halving when the model's trained context was requested (`-c 0`)
+/// and llama-server died before reporting the real size.
+const UNKNOWN_CTX_SIZE: u32 = 4096;
+
+/// Crashes of the managed server since it was started or stopped by the user.
+#[derive(Debug, Default)]
+pub struct CrashHistory {
+    times: VecDeque<Instant>,
+    oom_streak: u32,
+}
+
+/// Payload of the `llamacpp_server_crashed` event.
+#[derive(Clone, Debug, Serialize)]
+pub struct CrashReport {
+    pub model: String,
+    pub port: u16,
+    pub exit_code: Option<i32>,
+    /// Unix signal that killed the process (9 is usually the OOM killer).
+    pub signal: Option<i32>,
+    pub reason: String,
+    pub oom: bool,
+    pub log_tail: Vec<String>,
+    /// Crashes within the last 10 minutes, this one included.
+    pub crashes: u32,
+    /// Delay before the restart; `None` when the server is not restarted.
+    pub restart_in_ms: Option<u64>,
+    /// Reduced context size for the restart after repeated out-of-memory crashes.
+    pub ctx_size: Option<u32>,
+    /// Auto-restart is on but the crash-loop limit was reached.
+    pub gave_up: bool,
+}
+
+fn exit_signal(status: ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+fn is_oom_line(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    ["out of memory", "failed to allocate", "unable to allocate", "cudamalloc failed", "bad_alloc", "erroroutofdevicememory"]
+        .iter()
+        .any(|needle| lower.contains(needle))
+}
+
+/// 1 s, 2 s, 4 s, ... capped at a minute.
+fn backoff(crashes: u32) -> Duration {
+    FIRST_BACKOFF.saturating_mul(1 << crashes.saturating_sub(1).min(6)).min(MAX_BACKOFF)
+}
+
+/// Watches the server started as `generation` and handles its exit. Stops quietly once
+/// the server has been stopped or replaced, since those exits are intended.
+pub(super) fn supervise(app: tauri::AppHandle, generation: u64) {
+    tauri::async_runtime::spawn(async move {
+        let exit = loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let state = app.state::<LlamaServerState>();
+            let Ok(mut info) = state.0.lock() else { return; };
+            if info.generation != generation {
+                return;
+            }
+            match info.child.as_mut().map(|c| c.try_wait()) {
+                Some(Ok(Some(status))) => break status,
+                Some(Ok(None)) => {}
+                _ => return,
+            }
+        };
+        on_exit(app, generation, exit).await;
+    });
+}
+
+async fn on_exit(app: tauri::AppHandle, generation: u64, exit: ExitStatus) {
+    let settings = crate::db::load_stored_settings(&app).ok();
+    let auto_restart = settings.as_ref().and_then(|s| s.llama_auto_restart).unwrap_or(true);
+    let max_restarts = settings
+        .as_ref()
+        .and_then(|s| s.llama_max_restarts)
+        .and_then(|n| u32::try_from(n).ok())
+        .unwrap_or(DEFAULT_MAX_RESTARTS);
+
+    let (report, delay) = {
+        let state = app.state::<LlamaServerState>();
+        let Ok(mut info) = state.0.lock() else { return; };
+        if info.generation != generation {
+            return;
+        }
+        info.child = None;
+        let Some(spec) = info.spec.clone() else { return; };
+        let log_tail: Vec<String> = info.log_tail.iter().cloned().collect();
+        let signal = exit_signal(exit);
+        // Nothing in the app sends SIGKILL to a server it still supervises
+        let oom = signal == Some(9) || log_tail.iter().any(|l| is_oom_line(l));
+
+        let now = Instant::now();
+        let history = &mut info.crashes;
+        history.times.push_back(now);
+        while history.times.front().is_some_and(|t| now.duration_since(*t) > CRASH_WINDOW) {
+            history.times.pop_front();
+        }
+        history.oom_streak = if oom { history.oom_streak + 1 } else { 0 };
+        let crashes = history.times.len() as u32;
+        let gave_up = auto_restart && crashes > max_restarts;
+        let restart = auto_restart && !gave_up;
+
+        let mut ctx_size = None;
+        if restart && info.crashes.oom_streak >= OOM_FALLBACK_AFTER {
+            let current = info
+                .n_ctx
+                .or(spec.ctx_size)
+                .filter(|c| *c > 0)
+                .unwrap_or(UNKNOWN_CTX_SIZE);
+            let reduced = (current / 2).max(MIN_CTX_SIZE);
+            if reduced < current {
+                ctx_size = Some(reduced);
+                info.crashes.oom_streak = 0;
+                if let Some(spec) = info.spec.as_mut() {
+                    spec.ctx_size = Some(reduced);
+                }
+            }
+        }
+
+        let loaded = info.status.state == ServerPhase::Ready;
+        let what = if loaded { "llama-server crashed" } else { "llama-server exited before the model was loaded" };
+        let detail = info.last_error.clone().map(|e| format!(": {}", e)).unwrap_or_default();
+        let reason = format!("{} ({}){}", what, describe_exit(exit), detail);
+        let delay = restart.then(|| backoff(crashes));
+
+        info.status.state = if restart { ServerPhase::Restarting } else { ServerPhase::Failed };
+        info.status.progress = None;
+        info.status.pid = None;
+        info.status.reason = Some(if gave_up {
+            format!("{}; gave up after {} crashes in {} min", reason, crashes, CRASH_WINDOW.as_secs() / 60)
+        } else {
+            reason.clone()
+        });
+        status::emit(&app, &info.status);
+
+        let report = CrashReport {
+            model: spec.model_path,
+            port: spec.port,
+            exit_code: exit.code(),
+            signal,
+            reason,
+            oom,
+            log_tail,
+            crashes,
+            restart_in_ms: delay.map(|d| d.as_millis() as u64),
+            ctx_size,
+            gave_up,
+        };
+        (report, delay)
+    };
+
+    println!(
+        "[llama_cpp.watchdog] {} (oom={}, crashes={}, restart_in={:?}, ctx_size={:?})",
+        report.reason, report.oom, report.crashes, delay, report.ctx_size
+    );
+    let _ = app.emit(CRASH_EVENT, &report);
+
+    let Some(delay) = delay else {
+        if let Ok(pid_file) = pid_file(&app) {
+            let _ = std::fs::remove_file(pid_file);
+        }
+        return;
+    };
+    tokio::time::sleep(delay).await;
+    restart(&app, generation);
+}
+
+/// Starts the crashed server again unless it was stopped or started by hand meanwhile.
+fn restart(app: &tauri::AppHandle, generation: u64) {
+    let spec = {
+        let state = app.state::<LlamaServerState>();
+        let Ok(info) = state.0.lock() else { return; };
+        if info.generation != generation {
+            return;
+        }
+        match info.spec.clone() {
+            Some(spec) => spec,
+            None => return,
+        }
+    };
+    println!("[llama_cpp.watchdog] restarting '{}' on port {}", spec.model_path, spec.port);
+    let result = build_command(app, &spec.model_path, &spec.variant, spec.port, spec.ctx_size)
+        .and_then(|cmd| spawn_server(app, spec, cmd, Some(generation)));
+    if let Err(e) = result {
+        println!("[llama_cpp.watchdog] restart failed: {}", e);
+        let state = app.state::<LlamaServerState>();
+        let Ok(mut info) = state.0.lock() else { return; };
+        // Leave the status alone if the server was stopped or started by hand meanwhile
+        if info.generation == generation && info.status.state == ServerPhase::Restarting {
+            info.status.state = ServerPhase::Failed;
+            info.status.reason = Some(e);
+            status::emit(app, &info.status);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct OllamaTag { pub name: String }
@@ -6,14 +6,14 @@ pub struct OllamaTag { pub name: String }
 pub struct OllamaTagsResp { pub models: Vec<OllamaTag> }
 
 #[tauri::command]
-pub async fn list_ollama_models(base_url: String) -> Result<Vec<String>, String> {
+pub async fn list_ollama_models(app: tauri::AppHandle, base_url: String) -> Result<Vec<String>, String> {
     let base = if base_url.trim().is_empty() {
         "http://127.0.0.1:11434".to_string()
     } else {
         base_url.trim_end_matches('/').to_string()
     };
     let url = format!("{}/api/tags", base);
-    let client = reqwest::Client::new();
+    let client = crate::providers::http::client(&app)?;
     let resp = client.get(url).send().await.map_err(|e| e.to_string())?;
     if !resp.status().is_success() {
         return Err(format!("HTTP {}", resp.status()));
@@ -48,7 +48,7 @@ pub async fn pull_ollama_model(
         "stream": true
     });
 
-    let client = reqwest::Client::new();
+    let client = crate::providers::http::client(window.app_handle())?;
     let resp = client
         .post(url)
         .json(&body)
@@ -1,10 +1,18 @@
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use crate::attachments::ImageAttachment;
+use crate::providers::error::ProviderError;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ChatMessage { pub role: String, pub content: String }
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<ImageAttachment>>,
+}
 
-fn merge_json(a: &mut Value, b: &Value) {
+pub(crate) fn merge_json(a: &mut Value, b: &Value) {
     match (a, b) {
         (Value::Object(a_map), Value::Object(b_map)) => {
             for (k, v) in b_map {
@@ -26,8 +34,54 @@ fn merge_json(a: &mut Value, b: &Value) {
     }
 }
 
+/// Reads an `/api/chat` NDJSON stream back into the shape of a non-streaming reply:
+/// the final `done` object with the whole message.
+async fn collect_stream(response: reqwest::Response) -> Result<Value, ProviderError> {
+    let mut content = String::new();
+    let mut thinking = String::new();
+    let mut last = json!({});
+    let mut buf: Vec<u8> = Vec::new();
+    let mut stream = response.bytes_stream();
+
+    let mut handle_line = |line: &[u8]| -> Result<(), ProviderError> {
+        let Ok(v) = serde_json::from_slice::<Value>(line) else { return Ok(()); };
+        if v.get("error").is_some() {
+            return Err(ProviderError::from_stream_error(&v));
+        }
+        if let Some(token) = v.pointer("/message/content").and_then(|c| c.as_str()) {
+            content.push_str(token);
+        }
+        if let Some(token) = v.pointer("/message/thinking").and_then(|t| t.as_str()) {
+            thinking.push_str(token);
+        }
+        if v.get("done").and_then(|d| d.as_bool()).unwrap_or(false) {
+            last = v;
+        }
+        Ok(())
+    };
+
+    while let Some(item) = stream.next().await {
+        buf.extend_from_slice(&item?);
+        while let Some(pos) = buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=pos).collect();
+            handle_line(&line)?;
+        }
+    }
+    if !buf.is_empty() {
+        handle_line(&buf)?;
+    }
+
+    let mut message = json!({"role": "assistant", "content": content});
+    if !thinking.is_empty() {
+        message["thinking"] = json!(thinking);
+    }
+    last["message"] = message;
+    Ok(last)
+}
+
 #[tauri::command]
 pub async fn query_ollama(
+    app: tauri::AppHandle,
     base_url: String,
     model: String,
     prompt: String,
@@ -37,7 +91,12 @@ pub async fn query_ollama(
     top_p: Option<f32>,
     max_tokens: Option<i32>,
     advanced_params: Option<Value>,
-) -> Result<String, String> {
+    request_id: Option<String>,
+    response_schema: Option<Value>,
+    repair_attempts: Option<u32>,
+    project_id: Option<i64>,
+    bypass_cache: Option<bool>,
+) -> Result<String, ProviderError> {
     let msg_len = messages.as_ref().map(|m| m.len()).unwrap_or(0);
     println!("[ollama.query_ollama] base_url='{}', model='{}', prompt_len={}, messages_len={}", base_url, model, prompt.len(), msg_len);
     let base = if base_url.trim().is_empty() {
@@ -53,7 +112,24 @@ pub async fn query_ollama(
     let n_pred = max_tokens.unwrap_or(-1);
 
     let msgs_val = if let Some(list) = messages {
-        serde_json::to_value(list).unwrap_or_else(|_| json!([{"role":"user","content":prompt}]))
+        // The image limit applies to the whole request, not to each message
+        let total: usize = list.iter().map(|m| m.images.as_ref().map_or(0, Vec::len)).sum();
+        if total > crate::attachments::MAX_IMAGES {
+            return Err(format!("Too many images: {} (at most {} per request)", total, crate::attachments::MAX_IMAGES).into());
+        }
+        let mut out = Vec::with_capacity(list.len());
+        for mut m in list {
+            // Earlier reasoning is not sent back to the model
+            crate::reasoning::strip_history(&m.role, &mut m.content);
+            let mut v = json!({"role": m.role, "content": m.content});
+            // Ollama takes images as bare base64 strings next to the text
+            if let Some(images) = m.images.filter(|i| !i.is_empty()) {
+                let images = crate::attachments::prepare(images).await?;
+                v["images"] = images.iter().map(|i| json!(i.base64())).collect();
+            }
+            out.push(v);
+        }
+        Value::Array(out)
     } else {
         json!([{"role":"user","content":prompt}])
     };
@@ -61,7 +137,6 @@ pub async fn query_ollama(
     let mut body = json!({
         "model": model,
         "messages": msgs_val,
-        "stream": false,
         "options": {
             "temperature": temp,
             "top_k": tk,
@@ -73,14 +148,41 @@ pub async fn query_ollama(
     if let Some(extra) = advanced_params {
         merge_json(&mut body, &extra);
     }
+    // A schema passed explicitly wins over a `format` from advanced params
+    if let Some(schema) = &response_schema {
+        body["format"] = schema.clone();
+    }
+    // Streamed and reassembled: Ollama stops generating once a streaming client disconnects,
+    // which is what `cancel_generation` relies on
+    body["stream"] = json!(true);
 
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(url)
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    let cache = crate::cache::CacheSlot::for_body(&app, bypass_cache, &url, &body);
+    if let Some(hit) = cache.as_ref().and_then(|c| c.get(&app)) {
+        return Ok(hit);
+    }
+    let request_id = crate::streaming::ensure_request_id(request_id);
+    let client = crate::providers::http::client(&app)?;
+    let send = |body: Value, _attempt: u32| {
+        let url = url.clone();
+        let client = client.clone();
+        async move {
+            let resp = crate::providers::http::send_with_retry(|| client.post(&url).json(&body)).await?;
 
-    resp.text().await.map_err(|e| e.to_string())
+            let raw = collect_stream(resp).await?.to_string();
+            Ok(crate::reasoning::split_raw(raw, "/message", "thinking"))
+        }
+    };
+    let started = std::time::Instant::now();
+    let raw = match &response_schema {
+        Some(schema) => {
+            let validated = crate::schema::send_validated(body, schema, "/message/content", repair_attempts.unwrap_or(0), send);
+            crate::generation::run_cancellable(&app, &request_id, validated).await?
+        }
+        None => crate::generation::run_cancellable(&app, &request_id, send(body, 0)).await?,
+    };
+    crate::api::record_raw_usage(&app, project_id, "ollama", &model, &raw, started);
+    if let Some(cache) = &cache {
+        cache.put(&app, "ollama", &model, &raw);
+    }
+    Ok(raw)
 }
@@ -0,0 +1,96 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+const DEFAULT_TTL_SECS: i64 = 24 * 60 * 60;
+const DEFAULT_MAX_MB: i64 = 64;
+
+/// A cache entry a request may read from and write to. Only requests that should give
+/// the same answer every time get one: the cache must be enabled in settings, the caller
+/// must not bypass it, and sampling must be deterministic (temperature 0 or a fixed seed).
+pub struct CacheSlot {
+    key: String,
+    ttl_secs: i64,
+    max_bytes: i64,
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// JSON with object keys sorted, so equal requests hash equally whatever the field order.
+fn canonical(v: &Value) -> String {
+    match v {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|k| format!("{}:{}", Value::String(k.clone()), canonical(&map[k])))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => format!("[{}]", items.iter().map(canonical).collect::<Vec<_>>().join(",")),
+        other => other.to_string(),
+    }
+}
+
+/// Whether a request body samples deterministically: `temperature` (top-level or Ollama
+/// `options`) is 0, or a `seed` is fixed.
+pub fn is_deterministic(body: &Value) -> bool {
+    let field = |name: &str| body.get(name).or_else(|| body.pointer(&format!("/options/{}", name)));
+    let zero_temperature = field("temperature").and_then(|t| t.as_f64()).map(|t| t <= 0.0).unwrap_or(false);
+    let seeded = field("seed").map(|s| !s.is_null()).unwrap_or(false);
+    zero_temperature || seeded
+}
+
+impl CacheSlot {
+    /// Returns a slot for `parts` (everything that determines the answer: endpoint, model,
+    /// messages, sampling) when the request may use the cache.
+    /// `kind` separates entries of different shapes (raw bodies vs `ChatResponse`).
+    pub fn open(app: &tauri::AppHandle, bypass: Option<bool>, deterministic: bool, kind: &str, parts: &Value) -> Option<CacheSlot> {
+        if bypass.unwrap_or(false) || !deterministic {
+            return None;
+        }
+        let settings = crate::db::load_stored_settings(app).ok()?;
+        if !settings.response_cache_enabled.unwrap_or(false) {
+            return None;
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(kind.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(canonical(parts).as_bytes());
+        Some(CacheSlot {
+            key: format!("{:x}", hasher.finalize()),
+            ttl_secs: settings.response_cache_ttl_secs.filter(|t| *t > 0).unwrap_or(DEFAULT_TTL_SECS),
+            max_bytes: settings.response_cache_max_mb.filter(|m| *m > 0).unwrap_or(DEFAULT_MAX_MB) * 1024 * 1024,
+        })
+    }
+
+    /// Slot for a raw request `body` sent to `url`.
+    pub fn for_body(app: &tauri::AppHandle, bypass: Option<bool>, url: &str, body: &Value) -> Option<CacheSlot> {
+        Self::open(app, bypass, is_deterministic(body), "raw", &json!({"url": url, "body": body}))
+    }
+
+    pub fn get(&self, app: &tauri::AppHandle) -> Option<String> {
+        match crate::db::response_cache_get(app, &self.key, self.ttl_secs, now()) {
+            Ok(hit) => {
+                if hit.is_some() {
+                    println!("[cache.get] hit {}", &self.key[..12]);
+                }
+                hit
+            }
+            Err(e) => {
+                println!("[cache.get] lookup failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Stores a successful response. Failures are logged; the request has already succeeded.
+    pub fn put(&self, app: &tauri::AppHandle, provider: &str, model: &str, value: &str) {
+        if let Err(e) = crate::db::response_cache_put(app, &self.key, provider, model, value, self.ttl_secs, self.max_bytes, now()) {
+            println!("[cache.put] failed to store response: {}", e);
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use tauri::Manager;
+use crate::backends::llama_cpp::launch::{profile_key, LaunchProfile};
+use crate::secrets;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SettingsPayload {
@@ -13,6 +16,14 @@ pub struct SettingsPayload {
     // DeepSeek
     pub deepseek_url: String,
     pub deepseek_model: String,
+    // Anthropic
+    pub anthropic_base: Option<String>,
+    pub anthropic_key: Option<String>,
+    pub anthropic_model: Option<String>,
+    // Google Gemini
+    pub gemini_base: Option<String>,
+    pub gemini_key: Option<String>,
+    pub gemini_model: Option<String>,
     // Ollama
     pub ollama_base: Option<String>,
     pub ollama_model: Option<String>,
@@ -33,6 +44,29 @@ pub struct SettingsPayload {
     pub paste_to_file_length: Option<i64>,
     pub parse_pdf_as_image: Option<bool>,
     pub context_folder: Option<String>,
+    // Network: proxy URL (http://, https:// or socks5://), comma-separated no-proxy hosts,
+    // extra PEM CA bundle path, timeouts in seconds (0 = none) and User-Agent
+    pub http_proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub ca_bundle_path: Option<String>,
+    pub connect_timeout_secs: Option<i64>,
+    pub read_timeout_secs: Option<i64>,
+    pub user_agent: Option<String>,
+    // Response cache (opt-in): entry lifetime and total size limit
+    pub response_cache_enabled: Option<bool>,
+    pub response_cache_ttl_secs: Option<i64>,
+    pub response_cache_max_mb: Option<i64>,
+    // Local OpenAI-compatible HTTP gateway on 127.0.0.1
+    pub gateway_enabled: Option<bool>,
+    pub gateway_port: Option<i64>,
+    // Model-swapping llama-server router on 127.0.0.1: resident model limit and idle unload time
+    pub llama_router_enabled: Option<bool>,
+    pub llama_router_port: Option<i64>,
+    pub llama_router_max_models: Option<i64>,
+    pub llama_router_idle_ttl_secs: Option<i64>,
+    // llama-server watchdog: restart after crashes, crash limit within 10 minutes
+    pub llama_auto_restart: Option<bool>,
+    pub llama_max_restarts: Option<i64>,
     // Theme
     pub theme: String,
 }
@@ -62,16 +96,123 @@ pub struct ProjectRow {
     pub model: String,
     pub meta: Option<String>,
     pub created_at: String,
+    // Endpoint profile the project talks to; `provider`/`server` mirror it for older clients
+    pub provider_id: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectInput {
     pub name: String,
     pub work_mode: String,
+    // Filled from the profile when `provider_id` is set
+    #[serde(default)]
     pub provider: String,
+    #[serde(default)]
     pub server: String,
     pub model: String,
     pub meta: Option<String>,
+    #[serde(default)]
+    pub provider_id: Option<i64>,
+}
+
+/// Provider kinds a profile can point at.
+pub const PROVIDER_KINDS: &[&str] = &["openai", "deepseek", "anthropic", "gemini", "ollama", "local"];
+
+/// A named endpoint: provider kind, base URL, key, default model and extra request headers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderProfile {
+    pub id: i64,
+    pub name: String,
+    pub kind: String,
+    pub base_url: String,
+    pub api_key: String,
+    pub default_model: String,
+    pub extra_headers: HashMap<String, String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderProfileInput {
+    pub name: String,
+    pub kind: String,
+    #[serde(default)]
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub default_model: String,
+    #[serde(default)]
+    pub extra_headers: HashMap<String, String>,
+}
+
+/// One generation request, as written to the `usage` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub project_id: Option<i64>,
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub total_tokens: Option<i64>,
+    pub latency_ms: i64,
+}
+
+/// Price per million tokens for a provider/model pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub provider: String,
+    pub model: String,
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageAggregate {
+    // Day (YYYY-MM-DD), project id ("none" without a project) or "provider/model"
+    pub key: String,
+    pub label: String,
+    pub requests: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+    pub avg_latency_ms: f64,
+    pub cost: f64,
+    // Requests whose model has no price set; they add nothing to `cost`
+    pub unpriced_requests: i64,
+}
+
+/// Outcome of one target in a model comparison. Exactly one of `content`/`error` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompareResultRow {
+    pub target_index: i64,
+    pub provider: String,
+    pub model: String,
+    // Stream/cancel id of this target
+    pub request_id: String,
+    pub content: Option<String>,
+    pub error: Option<String>,
+    pub finish_reason: Option<String>,
+    pub latency_ms: i64,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub total_tokens: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompareRunRow {
+    pub id: i64,
+    pub project_id: Option<i64>,
+    // JSON array of the chat messages sent to every target
+    pub messages: String,
+    pub created_at: String,
+    pub results: Vec<CompareResultRow>,
+}
+
+/// llama-server launch options of one model file (see `profile_key`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LaunchProfileRow {
+    pub model: String,
+    pub profile: LaunchProfile,
 }
 
 fn db_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
@@ -131,6 +272,72 @@ pub fn init_db(app: &tauri::AppHandle) -> Result<(), String> {
             created_at TEXT NOT NULL DEFAULT (datetime('now')),
             FOREIGN KEY(project_id) REFERENCES projects(id) ON DELETE CASCADE
         );
+        CREATE TABLE IF NOT EXISTS usage (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            project_id INTEGER,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            prompt_tokens INTEGER,
+            completion_tokens INTEGER,
+            total_tokens INTEGER,
+            latency_ms INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_usage_created_at ON usage(created_at);
+        CREATE TABLE IF NOT EXISTS model_prices (
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            input_per_mtok REAL NOT NULL DEFAULT 0,
+            output_per_mtok REAL NOT NULL DEFAULT 0,
+            PRIMARY KEY (provider, model)
+        );
+        CREATE TABLE IF NOT EXISTS compare_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            project_id INTEGER,
+            messages TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE TABLE IF NOT EXISTS compare_results (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            run_id INTEGER NOT NULL,
+            target_index INTEGER NOT NULL,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            request_id TEXT NOT NULL,
+            content TEXT,
+            error TEXT,
+            finish_reason TEXT,
+            latency_ms INTEGER NOT NULL DEFAULT 0,
+            prompt_tokens INTEGER,
+            completion_tokens INTEGER,
+            total_tokens INTEGER,
+            FOREIGN KEY(run_id) REFERENCES compare_runs(id) ON DELETE CASCADE
+        );
+        CREATE TABLE IF NOT EXISTS response_cache (
+            key TEXT PRIMARY KEY,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            value TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            last_hit_at INTEGER NOT NULL,
+            hits INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE TABLE IF NOT EXISTS llama_launch_profiles (
+            model TEXT PRIMARY KEY,
+            profile TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE TABLE IF NOT EXISTS providers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            kind TEXT NOT NULL,
+            base_url TEXT NOT NULL DEFAULT '',
+            api_key TEXT NOT NULL DEFAULT '',
+            default_model TEXT NOT NULL DEFAULT '',
+            extra_headers TEXT NOT NULL DEFAULT '{}',
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
         "#,
     )
     .map_err(|e| e.to_string())?;
@@ -141,6 +348,8 @@ pub fn init_db(app: &tauri::AppHandle) -> Result<(), String> {
 
     // Migrate: add new columns if they do not exist
     migrate_settings_table(&conn)?;
+    migrate_projects_table(&conn)?;
+    migrate_plaintext_secrets(app, &conn)?;
 
     Ok(())
 }
@@ -204,13 +413,174 @@ fn migrate_settings_table(conn: &rusqlite::Connection) -> Result<(), String> {
     if !column_exists(conn, "settings", "ollama_params_json")? {
         add_col("ALTER TABLE settings ADD COLUMN ollama_params_json TEXT NOT NULL DEFAULT ''")?;
     }
+    if !column_exists(conn, "settings", "anthropic_base")? {
+        add_col("ALTER TABLE settings ADD COLUMN anthropic_base TEXT NOT NULL DEFAULT 'https://api.anthropic.com'")?;
+    }
+    if !column_exists(conn, "settings", "anthropic_key")? {
+        add_col("ALTER TABLE settings ADD COLUMN anthropic_key TEXT NOT NULL DEFAULT ''")?;
+    }
+    if !column_exists(conn, "settings", "anthropic_model")? {
+        add_col("ALTER TABLE settings ADD COLUMN anthropic_model TEXT NOT NULL DEFAULT 'claude-3-5-haiku-latest'")?;
+    }
+    if !column_exists(conn, "settings", "gemini_base")? {
+        add_col("ALTER TABLE settings ADD COLUMN gemini_base TEXT NOT NULL DEFAULT 'https://generativelanguage.googleapis.com/v1beta'")?;
+    }
+    if !column_exists(conn, "settings", "gemini_key")? {
+        add_col("ALTER TABLE settings ADD COLUMN gemini_key TEXT NOT NULL DEFAULT ''")?;
+    }
+    if !column_exists(conn, "settings", "gemini_model")? {
+        add_col("ALTER TABLE settings ADD COLUMN gemini_model TEXT NOT NULL DEFAULT 'gemini-1.5-flash'")?;
+    }
+    if !column_exists(conn, "settings", "http_proxy")? {
+        add_col("ALTER TABLE settings ADD COLUMN http_proxy TEXT NOT NULL DEFAULT ''")?;
+    }
+    if !column_exists(conn, "settings", "no_proxy")? {
+        add_col("ALTER TABLE settings ADD COLUMN no_proxy TEXT NOT NULL DEFAULT 'localhost,127.0.0.1,::1'")?;
+    }
+    if !column_exists(conn, "settings", "ca_bundle_path")? {
+        add_col("ALTER TABLE settings ADD COLUMN ca_bundle_path TEXT NOT NULL DEFAULT ''")?;
+    }
+    if !column_exists(conn, "settings", "connect_timeout_secs")? {
+        add_col("ALTER TABLE settings ADD COLUMN connect_timeout_secs INTEGER NOT NULL DEFAULT 20")?;
+    }
+    if !column_exists(conn, "settings", "read_timeout_secs")? {
+        add_col("ALTER TABLE settings ADD COLUMN read_timeout_secs INTEGER NOT NULL DEFAULT 300")?;
+    }
+    if !column_exists(conn, "settings", "user_agent")? {
+        add_col("ALTER TABLE settings ADD COLUMN user_agent TEXT NOT NULL DEFAULT ''")?;
+    }
+    if !column_exists(conn, "settings", "response_cache_enabled")? {
+        add_col("ALTER TABLE settings ADD COLUMN response_cache_enabled INTEGER NOT NULL DEFAULT 0")?;
+    }
+    if !column_exists(conn, "settings", "response_cache_ttl_secs")? {
+        add_col("ALTER TABLE settings ADD COLUMN response_cache_ttl_secs INTEGER NOT NULL DEFAULT 86400")?;
+    }
+    if !column_exists(conn, "settings", "response_cache_max_mb")? {
+        add_col("ALTER TABLE settings ADD COLUMN response_cache_max_mb INTEGER NOT NULL DEFAULT 64")?;
+    }
+    if !column_exists(conn, "settings", "gateway_enabled")? {
+        add_col("ALTER TABLE settings ADD COLUMN gateway_enabled INTEGER NOT NULL DEFAULT 0")?;
+    }
+    if !column_exists(conn, "settings", "gateway_port")? {
+        add_col("ALTER TABLE settings ADD COLUMN gateway_port INTEGER NOT NULL DEFAULT 8765")?;
+    }
+    // Bearer token of the gateway, encrypted like API keys; never part of `SettingsPayload`
+    if !column_exists(conn, "settings", "gateway_token")? {
+        add_col("ALTER TABLE settings ADD COLUMN gateway_token TEXT NOT NULL DEFAULT ''")?;
+    }
+    if !column_exists(conn, "settings", "llama_router_enabled")? {
+        add_col("ALTER TABLE settings ADD COLUMN llama_router_enabled INTEGER NOT NULL DEFAULT 0")?;
+    }
+    if !column_exists(conn, "settings", "llama_router_port")? {
+        add_col("ALTER TABLE settings ADD COLUMN llama_router_port INTEGER NOT NULL DEFAULT 8090")?;
+    }
+    if !column_exists(conn, "settings", "llama_router_max_models")? {
+        add_col("ALTER TABLE settings ADD COLUMN llama_router_max_models INTEGER NOT NULL DEFAULT 1")?;
+    }
+    if !column_exists(conn, "settings", "llama_router_idle_ttl_secs")? {
+        add_col("ALTER TABLE settings ADD COLUMN llama_router_idle_ttl_secs INTEGER NOT NULL DEFAULT 600")?;
+    }
+    if !column_exists(conn, "settings", "llama_auto_restart")? {
+        add_col("ALTER TABLE settings ADD COLUMN llama_auto_restart INTEGER NOT NULL DEFAULT 1")?;
+    }
+    if !column_exists(conn, "settings", "llama_max_restarts")? {
+        add_col("ALTER TABLE settings ADD COLUMN llama_max_restarts INTEGER NOT NULL DEFAULT 5")?;
+    }
     Ok(())
 }
 
+/// Adds `projects.provider_id` and links existing projects to endpoint profiles.
+/// Each distinct free-text provider/server pair becomes one profile; cloud profiles
+/// take the key currently stored in settings.
+fn migrate_projects_table(conn: &rusqlite::Connection) -> Result<(), String> {
+    if column_exists(conn, "projects", "provider_id")? {
+        return Ok(());
+    }
+    conn.execute("ALTER TABLE projects ADD COLUMN provider_id INTEGER REFERENCES providers(id) ON DELETE SET NULL", [])
+        .map_err(|e| e.to_string())?;
+
+    let api_key: String = conn
+        .query_row("SELECT api_key FROM settings WHERE id = 1", [], |r| r.get(0))
+        .unwrap_or_default();
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT provider, server FROM projects")
+        .map_err(|e| e.to_string())?;
+    let pairs: Vec<(String, String)> = stmt
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    for (provider, server) in pairs {
+        let (kind, base_url, key) = match provider.as_str() {
+            "openai" | "deepseek" => (provider.as_str(), server.clone(), api_key.as_str()),
+            "ollama" => ("ollama", server.clone(), ""),
+            // Local projects store "llama.cpp:{variant}:{port}"
+            "llama" | "local" => {
+                let port = server.rsplit(':').next().and_then(|p| p.parse::<u16>().ok()).unwrap_or(8080);
+                ("local", format!("http://127.0.0.1:{}", port), "")
+            }
+            other => {
+                println!("[db.migrate_projects_table] Skipping unknown provider '{}'", other);
+                continue;
+            }
+        };
+        let name = format!("{} ({})", provider, server);
+        conn.execute(
+            "INSERT INTO providers (name, kind, base_url, api_key) VALUES (?, ?, ?, ?) ON CONFLICT(name) DO NOTHING",
+            rusqlite::params![name, kind, base_url, key],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE projects SET provider_id = (SELECT id FROM providers WHERE name = ?) WHERE provider = ? AND server = ?",
+            rusqlite::params![name, provider, server],
+        )
+        .map_err(|e| e.to_string())?;
+        println!("[db.migrate_projects_table] Linked '{}' projects to profile '{}'", provider, name);
+    }
+    Ok(())
+}
+
+/// Encrypts API keys that are still stored in plain text (settings and provider profiles).
+fn migrate_plaintext_secrets(app: &tauri::AppHandle, conn: &rusqlite::Connection) -> Result<(), String> {
+    let targets = [
+        ("settings", "api_key"),
+        ("settings", "anthropic_key"),
+        ("settings", "gemini_key"),
+        ("providers", "api_key"),
+    ];
+    for (table, col) in targets {
+        let mut stmt = conn
+            .prepare(&format!("SELECT rowid, {col} FROM {table} WHERE {col} IS NOT NULL AND {col} != '' AND {col} NOT LIKE 'enc:%'"))
+            .map_err(|e| e.to_string())?;
+        let plain: Vec<(i64, String)> = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        for (rowid, value) in plain {
+            let sealed = secrets::encrypt(app, &value)?;
+            conn.execute(&format!("UPDATE {table} SET {col} = ? WHERE rowid = ?"), rusqlite::params![sealed, rowid])
+                .map_err(|e| e.to_string())?;
+            println!("[db.migrate_plaintext_secrets] Encrypted {}.{} (row {})", table, col, rowid);
+        }
+    }
+    Ok(())
+}
+
+/// Settings for the frontend: API keys are replaced by a mask.
 #[tauri::command]
 pub fn load_settings(app: tauri::AppHandle) -> Result<SettingsPayload, String> {
     println!("[db.load_settings] Loading settings...");
-    let conn = ensure_conn(&app)?;
+    let mut settings = load_stored_settings(&app)?;
+    settings.api_key = secrets::mask(&settings.api_key);
+    settings.anthropic_key = settings.anthropic_key.map(|k| secrets::mask(&k));
+    settings.gemini_key = settings.gemini_key.map(|k| secrets::mask(&k));
+    Ok(settings)
+}
+
+/// Settings as stored, with API keys still encrypted. The provider layer decrypts them.
+pub fn load_stored_settings(app: &tauri::AppHandle) -> Result<SettingsPayload, String> {
+    let conn = ensure_conn(app)?;
     let mut stmt = conn
         .prepare(
             r#"SELECT 
@@ -222,7 +592,14 @@ pub fn load_settings(app: tauri::AppHandle) -> Result<SettingsPayload, String> {
                 temperature, top_k, top_p, min_p, max_tokens, repeat_last_n,
                 paste_to_file_length, parse_pdf_as_image,
                 context_folder,
-                theme
+                anthropic_base, anthropic_key, anthropic_model,
+                gemini_base, gemini_key, gemini_model,
+                theme,
+                http_proxy, no_proxy, ca_bundle_path, connect_timeout_secs, read_timeout_secs, user_agent,
+                response_cache_enabled, response_cache_ttl_secs, response_cache_max_mb,
+                gateway_enabled, gateway_port,
+                llama_router_enabled, llama_router_port, llama_router_max_models, llama_router_idle_ttl_secs,
+                llama_auto_restart, llama_max_restarts
             FROM settings WHERE id = 1"#, 
         )
         .map_err(|e| e.to_string())?;
@@ -251,7 +628,30 @@ pub fn load_settings(app: tauri::AppHandle) -> Result<SettingsPayload, String> {
                 paste_to_file_length: r.get(19).ok(),
                 parse_pdf_as_image: r.get::<_, i64>(20).ok().map(|v| v != 0),
                 context_folder: r.get(21).ok(),
-                theme: r.get(22)?,
+                anthropic_base: r.get(22).ok(),
+                anthropic_key: r.get(23).ok(),
+                anthropic_model: r.get(24).ok(),
+                gemini_base: r.get(25).ok(),
+                gemini_key: r.get(26).ok(),
+                gemini_model: r.get(27).ok(),
+                theme: r.get(28)?,
+                http_proxy: r.get(29).ok(),
+                no_proxy: r.get(30).ok(),
+                ca_bundle_path: r.get(31).ok(),
+                connect_timeout_secs: r.get(32).ok(),
+                read_timeout_secs: r.get(33).ok(),
+                user_agent: r.get(34).ok(),
+                response_cache_enabled: r.get::<_, i64>(35).ok().map(|v| v != 0),
+                response_cache_ttl_secs: r.get(36).ok(),
+                response_cache_max_mb: r.get(37).ok(),
+                gateway_enabled: r.get::<_, i64>(38).ok().map(|v| v != 0),
+                gateway_port: r.get(39).ok(),
+                llama_router_enabled: r.get::<_, i64>(40).ok().map(|v| v != 0),
+                llama_router_port: r.get(41).ok(),
+                llama_router_max_models: r.get(42).ok(),
+                llama_router_idle_ttl_secs: r.get(43).ok(),
+                llama_auto_restart: r.get::<_, i64>(44).ok().map(|v| v != 0),
+                llama_max_restarts: r.get(45).ok(),
             })
         })
         .map_err(|e| e.to_string())?;
@@ -261,10 +661,20 @@ pub fn load_settings(app: tauri::AppHandle) -> Result<SettingsPayload, String> {
 #[tauri::command]
 pub fn save_settings(app: tauri::AppHandle, settings: SettingsPayload) -> Result<(), String> {
     println!("[db.save_settings] Saving settings (mode='{}', model_file={:?})", settings.mode, settings.model_file);
+    // A masked key means "unchanged"; anything else is encrypted before it is written
+    let seal = |key: Option<&str>| -> Result<Option<String>, String> {
+        match key {
+            Some(k) if !secrets::is_mask(k) => secrets::encrypt(&app, k).map(Some),
+            _ => Ok(None),
+        }
+    };
+    let api_key = seal(Some(&settings.api_key))?;
+    let anthropic_key = seal(settings.anthropic_key.as_deref())?;
+    let gemini_key = seal(settings.gemini_key.as_deref())?;
     let conn = ensure_conn(&app)?;
     conn.execute(
         "UPDATE settings SET \
-            mode=?, api_key=?, api_base=?, api_model=?, \
+            mode=?, api_key=COALESCE(?, api_key), api_base=?, api_model=?, \
             deepseek_url=?, deepseek_model=?, \
             ollama_base=COALESCE(?, ollama_base), ollama_model=COALESCE(?, ollama_model), ollama_params_json=COALESCE(?, ollama_params_json), \
             model_repo=?, model_file=COALESCE(?, model_file), \
@@ -272,11 +682,20 @@ pub fn save_settings(app: tauri::AppHandle, settings: SettingsPayload) -> Result
             temperature=COALESCE(?, temperature), top_k=COALESCE(?, top_k), top_p=COALESCE(?, top_p), min_p=COALESCE(?, min_p), max_tokens=COALESCE(?, max_tokens), repeat_last_n=COALESCE(?, repeat_last_n), \
             paste_to_file_length=COALESCE(?, paste_to_file_length), parse_pdf_as_image=COALESCE(?, parse_pdf_as_image), \
             context_folder=COALESCE(?, context_folder), \
+            anthropic_base=COALESCE(?, anthropic_base), anthropic_key=COALESCE(?, anthropic_key), anthropic_model=COALESCE(?, anthropic_model), \
+            gemini_base=COALESCE(?, gemini_base), gemini_key=COALESCE(?, gemini_key), gemini_model=COALESCE(?, gemini_model), \
+            http_proxy=COALESCE(?, http_proxy), no_proxy=COALESCE(?, no_proxy), ca_bundle_path=COALESCE(?, ca_bundle_path), \
+            connect_timeout_secs=COALESCE(?, connect_timeout_secs), read_timeout_secs=COALESCE(?, read_timeout_secs), user_agent=COALESCE(?, user_agent), \
+            response_cache_enabled=COALESCE(?, response_cache_enabled), response_cache_ttl_secs=COALESCE(?, response_cache_ttl_secs), response_cache_max_mb=COALESCE(?, response_cache_max_mb), \
+            gateway_enabled=COALESCE(?, gateway_enabled), gateway_port=COALESCE(?, gateway_port), \
+            llama_router_enabled=COALESCE(?, llama_router_enabled), llama_router_port=COALESCE(?, llama_router_port), \
+            llama_router_max_models=COALESCE(?, llama_router_max_models), llama_router_idle_ttl_secs=COALESCE(?, llama_router_idle_ttl_secs), \
+            llama_auto_restart=COALESCE(?, llama_auto_restart), llama_max_restarts=COALESCE(?, llama_max_restarts), \
             theme=? \
          WHERE id=1",
         rusqlite::params![
             settings.mode,
-            settings.api_key,
+            api_key,
             settings.api_base,
             settings.api_model,
             settings.deepseek_url,
@@ -297,10 +716,42 @@ pub fn save_settings(app: tauri::AppHandle, settings: SettingsPayload) -> Result
             settings.paste_to_file_length,
             settings.parse_pdf_as_image.map(|b| if b {1} else {0}),
             settings.context_folder,
+            settings.anthropic_base,
+            anthropic_key,
+            settings.anthropic_model,
+            settings.gemini_base,
+            gemini_key,
+            settings.gemini_model,
+            settings.http_proxy,
+            settings.no_proxy,
+            settings.ca_bundle_path,
+            settings.connect_timeout_secs,
+            settings.read_timeout_secs,
+            settings.user_agent,
+            settings.response_cache_enabled.map(|b| if b {1} else {0}),
+            settings.response_cache_ttl_secs,
+            settings.response_cache_max_mb,
+            settings.gateway_enabled.map(|b| if b {1} else {0}),
+            settings.gateway_port,
+            settings.llama_router_enabled.map(|b| if b {1} else {0}),
+            settings.llama_router_port,
+            settings.llama_router_max_models,
+            settings.llama_router_idle_ttl_secs,
+            settings.llama_auto_restart.map(|b| if b {1} else {0}),
+            settings.llama_max_restarts,
             settings.theme,
         ],
     )
     .map_err(|e| e.to_string())?;
+    // Network settings may have changed
+    crate::providers::http::reset_client(&app);
+    // Start, stop or move the gateway to match the saved settings
+    if let Err(e) = crate::gateway::apply_settings(&app) {
+        println!("[db.save_settings] gateway: {}", e);
+    }
+    if let Err(e) = crate::backends::llama_cpp::router::apply_settings(&app) {
+        println!("[db.save_settings] llama router: {}", e);
+    }
     Ok(())
 }
 
@@ -385,9 +836,9 @@ pub fn list_projects(app: tauri::AppHandle, work_mode: Option<String>) -> Result
     println!("[db.list_projects] Listing projects (work_mode={:?})", work_mode);
     let conn = ensure_conn(&app)?;
     let q = if work_mode.is_some() {
-        "SELECT id, name, work_mode, provider, server, model, meta, created_at FROM projects WHERE work_mode = ? ORDER BY created_at DESC"
+        "SELECT id, name, work_mode, provider, server, model, meta, created_at, provider_id FROM projects WHERE work_mode = ? ORDER BY created_at DESC"
     } else {
-        "SELECT id, name, work_mode, provider, server, model, meta, created_at FROM projects ORDER BY created_at DESC"
+        "SELECT id, name, work_mode, provider, server, model, meta, created_at, provider_id FROM projects ORDER BY created_at DESC"
     };
     let mut stmt = conn.prepare(q).map_err(|e| e.to_string())?;
     let mut rows = if let Some(wm) = work_mode {
@@ -406,6 +857,7 @@ pub fn list_projects(app: tauri::AppHandle, work_mode: Option<String>) -> Result
             model: r.get(5).map_err(|e| e.to_string())?,
             meta: r.get(6).ok(),
             created_at: r.get(7).map_err(|e| e.to_string())?,
+            provider_id: r.get(8).map_err(|e| e.to_string())?,
         });
     }
     Ok(out)
@@ -413,16 +865,52 @@ pub fn list_projects(app: tauri::AppHandle, work_mode: Option<String>) -> Result
 
 #[tauri::command]
 pub fn save_project(app: tauri::AppHandle, project: ProjectInput) -> Result<i64, String> {
-    println!("[db.save_project] Saving project '{}' (mode={}, provider={}, server={}, model={})", project.name, project.work_mode, project.provider, project.server, project.model);
+    println!("[db.save_project] Saving project '{}' (mode={}, provider={}, server={}, model={}, provider_id={:?})", project.name, project.work_mode, project.provider, project.server, project.model, project.provider_id);
     if project.name.trim().is_empty() { return Err("Project name cannot be empty".to_string()); }
+    let mut project = project;
+    if let Some(id) = project.provider_id {
+        let profile = load_provider_profile(&app, id)?;
+        if project.provider.trim().is_empty() { project.provider = profile.kind; }
+        if project.server.trim().is_empty() { project.server = profile.base_url; }
+        if project.model.trim().is_empty() { project.model = profile.default_model; }
+    }
     let conn = ensure_conn(&app)?;
     conn.execute(
-        "INSERT INTO projects (name, work_mode, provider, server, model, meta) VALUES (?, ?, ?, ?, ?, ?)",
-        rusqlite::params![project.name, project.work_mode, project.provider, project.server, project.model, project.meta],
+        "INSERT INTO projects (name, work_mode, provider, server, model, meta, provider_id) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![project.name, project.work_mode, project.provider, project.server, project.model, project.meta, project.provider_id],
     ).map_err(|e| e.to_string())?;
     Ok(conn.last_insert_rowid())
 }
 
+/// Points a project at another endpoint profile (or none).
+#[tauri::command]
+pub fn set_project_provider(app: tauri::AppHandle, project_id: i64, provider_id: Option<i64>) -> Result<(), String> {
+    println!("[db.set_project_provider] project_id={}, provider_id={:?}", project_id, provider_id);
+    let conn = ensure_conn(&app)?;
+    let changed = match provider_id {
+        Some(id) => {
+            let profile = load_provider_profile(&app, id)?;
+            conn.execute(
+                "UPDATE projects SET provider_id = ?, provider = ?, server = ? WHERE id = ?",
+                rusqlite::params![id, profile.kind, profile.base_url, project_id],
+            )
+        }
+        None => conn.execute("UPDATE projects SET provider_id = NULL WHERE id = ?", rusqlite::params![project_id]),
+    }
+    .map_err(|e| e.to_string())?;
+    if changed == 0 {
+        return Err(format!("Project {} not found", project_id));
+    }
+    Ok(())
+}
+
+/// Endpoint profile id of a project, if it has one.
+pub fn project_provider_id(app: &tauri::AppHandle, project_id: i64) -> Result<Option<i64>, String> {
+    let conn = ensure_conn(app)?;
+    conn.query_row("SELECT provider_id FROM projects WHERE id = ?", rusqlite::params![project_id], |r| r.get(0))
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn delete_project(app: tauri::AppHandle, id: i64) -> Result<(), String> {
     println!("[db.delete_project] Deleting project id={} ", id);
@@ -431,3 +919,491 @@ pub fn delete_project(app: tauri::AppHandle, id: i64) -> Result<(), String> {
         .map_err(|e| e.to_string())?;
     Ok(())
 }
+
+fn provider_profile_from_row(r: &rusqlite::Row) -> Result<ProviderProfile, String> {
+    let headers: String = r.get(6).map_err(|e| e.to_string())?;
+    Ok(ProviderProfile {
+        id: r.get(0).map_err(|e| e.to_string())?,
+        name: r.get(1).map_err(|e| e.to_string())?,
+        kind: r.get(2).map_err(|e| e.to_string())?,
+        base_url: r.get(3).map_err(|e| e.to_string())?,
+        api_key: r.get(4).map_err(|e| e.to_string())?,
+        default_model: r.get(5).map_err(|e| e.to_string())?,
+        extra_headers: serde_json::from_str(&headers).unwrap_or_default(),
+        created_at: r.get(7).map_err(|e| e.to_string())?,
+    })
+}
+
+const PROVIDER_COLUMNS: &str = "id, name, kind, base_url, api_key, default_model, extra_headers, created_at";
+
+/// Profiles for the frontend; `api_key` is masked.
+#[tauri::command]
+pub fn list_provider_profiles(app: tauri::AppHandle) -> Result<Vec<ProviderProfile>, String> {
+    println!("[db.list_provider_profiles] Listing provider profiles");
+    let conn = ensure_conn(&app)?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM providers ORDER BY name", PROVIDER_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+        let mut profile = provider_profile_from_row(r)?;
+        profile.api_key = secrets::mask(&profile.api_key);
+        out.push(profile);
+    }
+    Ok(out)
+}
+
+/// Loads one profile as stored (the key stays encrypted) for the provider layer.
+pub fn load_provider_profile(app: &tauri::AppHandle, id: i64) -> Result<ProviderProfile, String> {
+    let conn = ensure_conn(app)?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM providers WHERE id = ?", PROVIDER_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(rusqlite::params![id]).map_err(|e| e.to_string())?;
+    match rows.next().map_err(|e| e.to_string())? {
+        Some(r) => provider_profile_from_row(r),
+        None => Err(format!("Provider profile {} not found", id)),
+    }
+}
+
+/// Creates a profile, or updates profile `id` when given. Returns the profile id.
+/// A masked `api_key` keeps the stored key.
+#[tauri::command]
+pub fn save_provider_profile(app: tauri::AppHandle, id: Option<i64>, profile: ProviderProfileInput) -> Result<i64, String> {
+    println!("[db.save_provider_profile] id={:?}, name='{}', kind='{}', base_url='{}'", id, profile.name, profile.kind, profile.base_url);
+    let name = profile.name.trim();
+    if name.is_empty() { return Err("Profile name cannot be empty".to_string()); }
+    let kind = profile.kind.trim().to_lowercase();
+    if !PROVIDER_KINDS.contains(&kind.as_str()) {
+        return Err(format!("Unknown provider kind '{}', expected one of: {}", profile.kind, PROVIDER_KINDS.join(", ")));
+    }
+    let base_url = profile.base_url.trim();
+    if !base_url.is_empty() && !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+        return Err("Base URL must start with http:// or https://".to_string());
+    }
+    let headers = serde_json::to_string(&profile.extra_headers).map_err(|e| e.to_string())?;
+    let api_key = if secrets::is_mask(&profile.api_key) { None } else { Some(secrets::encrypt(&app, &profile.api_key)?) };
+    let conn = ensure_conn(&app)?;
+    let result = match id {
+        Some(id) => conn
+            .execute(
+                "UPDATE providers SET name = ?, kind = ?, base_url = ?, api_key = COALESCE(?, api_key), default_model = ?, extra_headers = ? WHERE id = ?",
+                rusqlite::params![name, kind, base_url, api_key, profile.default_model.trim(), headers, id],
+            )
+            .and_then(|changed| if changed == 0 { Err(rusqlite::Error::QueryReturnedNoRows) } else { Ok(id) }),
+        None => conn
+            .execute(
+                "INSERT INTO providers (name, kind, base_url, api_key, default_model, extra_headers) VALUES (?, ?, ?, ?, ?, ?)",
+                rusqlite::params![name, kind, base_url, api_key.unwrap_or_default(), profile.default_model.trim(), headers],
+            )
+            .map(|_| conn.last_insert_rowid()),
+    };
+    match result {
+        Ok(id) => Ok(id),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Err(format!("Provider profile {} not found", id.unwrap_or_default())),
+        Err(e) if e.to_string().contains("UNIQUE") => Err(format!("A provider profile named '{}' already exists", name)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Deletes a profile; projects that used it keep their settings but lose the link.
+#[tauri::command]
+pub fn delete_provider_profile(app: tauri::AppHandle, id: i64) -> Result<(), String> {
+    println!("[db.delete_provider_profile] id={}", id);
+    let conn = ensure_conn(&app)?;
+    conn.execute("UPDATE projects SET provider_id = NULL WHERE provider_id = ?", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM providers WHERE id = ?", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Writes one request to the usage ledger. Callers log failures instead of failing the request.
+pub fn record_usage(app: &tauri::AppHandle, record: &UsageRecord) -> Result<(), String> {
+    let conn = ensure_conn(app)?;
+    conn.execute(
+        "INSERT INTO usage (project_id, provider, model, prompt_tokens, completion_tokens, total_tokens, latency_ms) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            record.project_id,
+            record.provider,
+            record.model,
+            record.prompt_tokens,
+            record.completion_tokens,
+            record.total_tokens,
+            record.latency_ms,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_model_prices(app: tauri::AppHandle) -> Result<Vec<ModelPrice>, String> {
+    println!("[db.list_model_prices] Listing model prices");
+    let conn = ensure_conn(&app)?;
+    let mut stmt = conn
+        .prepare("SELECT provider, model, input_per_mtok, output_per_mtok FROM model_prices ORDER BY provider, model")
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+        out.push(ModelPrice {
+            provider: r.get(0).map_err(|e| e.to_string())?,
+            model: r.get(1).map_err(|e| e.to_string())?,
+            input_per_mtok: r.get(2).map_err(|e| e.to_string())?,
+            output_per_mtok: r.get(3).map_err(|e| e.to_string())?,
+        });
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn save_model_price(app: tauri::AppHandle, price: ModelPrice) -> Result<(), String> {
+    println!("[db.save_model_price] provider='{}', model='{}', in={}, out={}", price.provider, price.model, price.input_per_mtok, price.output_per_mtok);
+    if price.provider.trim().is_empty() || price.model.trim().is_empty() {
+        return Err("Provider and model cannot be empty".to_string());
+    }
+    if price.input_per_mtok < 0.0 || price.output_per_mtok < 0.0 {
+        return Err("Prices cannot be negative".to_string());
+    }
+    let conn = ensure_conn(&app)?;
+    conn.execute(
+        "INSERT INTO model_prices (provider, model, input_per_mtok, output_per_mtok) VALUES (?, ?, ?, ?)
+         ON CONFLICT(provider, model) DO UPDATE SET input_per_mtok = excluded.input_per_mtok, output_per_mtok = excluded.output_per_mtok",
+        rusqlite::params![price.provider.trim(), price.model.trim(), price.input_per_mtok, price.output_per_mtok],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_model_price(app: tauri::AppHandle, provider: String, model: String) -> Result<(), String> {
+    println!("[db.delete_model_price] provider='{}', model='{}'", provider, model);
+    let conn = ensure_conn(&app)?;
+    conn.execute("DELETE FROM model_prices WHERE provider = ? AND model = ?", rusqlite::params![provider, model])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Aggregates the usage ledger by `group_by`: "day", "project" or "model".
+/// `from`/`to` are inclusive dates (YYYY-MM-DD). Cost uses the current price table.
+#[tauri::command]
+pub fn usage_summary(
+    app: tauri::AppHandle,
+    group_by: String,
+    from: Option<String>,
+    to: Option<String>,
+    project_id: Option<i64>,
+) -> Result<Vec<UsageAggregate>, String> {
+    println!("[db.usage_summary] group_by='{}', from={:?}, to={:?}, project_id={:?}", group_by, from, to, project_id);
+    let (key, label) = match group_by.as_str() {
+        "day" => ("date(u.created_at)", "date(u.created_at)"),
+        "project" => (
+            "COALESCE(CAST(u.project_id AS TEXT), 'none')",
+            "COALESCE(p.name, CASE WHEN u.project_id IS NULL THEN 'No project' ELSE 'Deleted project' END)",
+        ),
+        "model" => ("u.provider || '/' || u.model", "u.provider || '/' || u.model"),
+        other => return Err(format!("Unknown group_by '{}', expected day, project or model", other)),
+    };
+
+    let mut filters: Vec<&str> = Vec::new();
+    let mut params: Vec<rusqlite::types::Value> = Vec::new();
+    if let Some(from) = from.filter(|s| !s.trim().is_empty()) {
+        filters.push("date(u.created_at) >= date(?)");
+        params.push(from.into());
+    }
+    if let Some(to) = to.filter(|s| !s.trim().is_empty()) {
+        filters.push("date(u.created_at) <= date(?)");
+        params.push(to.into());
+    }
+    if let Some(id) = project_id {
+        filters.push("u.project_id = ?");
+        params.push(id.into());
+    }
+    let where_clause = if filters.is_empty() { String::new() } else { format!("WHERE {}", filters.join(" AND ")) };
+
+    let q = format!(
+        "SELECT {key} AS k, MAX({label}), COUNT(*),
+                COALESCE(SUM(u.prompt_tokens), 0), COALESCE(SUM(u.completion_tokens), 0), COALESCE(SUM(u.total_tokens), 0),
+                COALESCE(AVG(u.latency_ms), 0),
+                COALESCE(SUM(COALESCE(u.prompt_tokens, 0) * mp.input_per_mtok + COALESCE(u.completion_tokens, 0) * mp.output_per_mtok), 0) / 1000000.0,
+                SUM(CASE WHEN mp.model IS NULL THEN 1 ELSE 0 END)
+         FROM usage u
+         LEFT JOIN projects p ON p.id = u.project_id
+         LEFT JOIN model_prices mp ON mp.provider = u.provider AND mp.model = u.model
+         {where_clause}
+         GROUP BY k
+         ORDER BY k DESC",
+    );
+    let conn = ensure_conn(&app)?;
+    let mut stmt = conn.prepare(&q).map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(rusqlite::params_from_iter(params)).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+        out.push(UsageAggregate {
+            key: r.get(0).map_err(|e| e.to_string())?,
+            label: r.get(1).map_err(|e| e.to_string())?,
+            requests: r.get(2).map_err(|e| e.to_string())?,
+            prompt_tokens: r.get(3).map_err(|e| e.to_string())?,
+            completion_tokens: r.get(4).map_err(|e| e.to_string())?,
+            total_tokens: r.get(5).map_err(|e| e.to_string())?,
+            avg_latency_ms: r.get(6).map_err(|e| e.to_string())?,
+            cost: r.get(7).map_err(|e| e.to_string())?,
+            unpriced_requests: r.get(8).map_err(|e| e.to_string())?,
+        });
+    }
+    Ok(out)
+}
+
+/// Stores a finished comparison with its per-target results.
+pub fn save_compare_run(
+    app: &tauri::AppHandle,
+    project_id: Option<i64>,
+    messages: &str,
+    results: &[CompareResultRow],
+) -> Result<i64, String> {
+    let mut conn = ensure_conn(app)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("INSERT INTO compare_runs (project_id, messages) VALUES (?, ?)", rusqlite::params![project_id, messages])
+        .map_err(|e| e.to_string())?;
+    let run_id = tx.last_insert_rowid();
+    for r in results {
+        tx.execute(
+            "INSERT INTO compare_results (run_id, target_index, provider, model, request_id, content, error, finish_reason, latency_ms, prompt_tokens, completion_tokens, total_tokens)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![
+                run_id,
+                r.target_index,
+                r.provider,
+                r.model,
+                r.request_id,
+                r.content,
+                r.error,
+                r.finish_reason,
+                r.latency_ms,
+                r.prompt_tokens,
+                r.completion_tokens,
+                r.total_tokens,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(run_id)
+}
+
+fn load_compare_results(conn: &rusqlite::Connection, run_id: i64) -> Result<Vec<CompareResultRow>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT target_index, provider, model, request_id, content, error, finish_reason, latency_ms, prompt_tokens, completion_tokens, total_tokens
+             FROM compare_results WHERE run_id = ? ORDER BY target_index",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(rusqlite::params![run_id]).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+        out.push(CompareResultRow {
+            target_index: r.get(0).map_err(|e| e.to_string())?,
+            provider: r.get(1).map_err(|e| e.to_string())?,
+            model: r.get(2).map_err(|e| e.to_string())?,
+            request_id: r.get(3).map_err(|e| e.to_string())?,
+            content: r.get(4).map_err(|e| e.to_string())?,
+            error: r.get(5).map_err(|e| e.to_string())?,
+            finish_reason: r.get(6).map_err(|e| e.to_string())?,
+            latency_ms: r.get(7).map_err(|e| e.to_string())?,
+            prompt_tokens: r.get(8).map_err(|e| e.to_string())?,
+            completion_tokens: r.get(9).map_err(|e| e.to_string())?,
+            total_tokens: r.get(10).map_err(|e| e.to_string())?,
+        });
+    }
+    Ok(out)
+}
+
+/// Past comparisons, newest first, each with its results.
+#[tauri::command]
+pub fn list_compare_runs(app: tauri::AppHandle, project_id: Option<i64>, limit: Option<i64>) -> Result<Vec<CompareRunRow>, String> {
+    println!("[db.list_compare_runs] project_id={:?}, limit={:?}", project_id, limit);
+    let conn = ensure_conn(&app)?;
+    let mut stmt = conn
+        .prepare("SELECT id, project_id, messages, created_at FROM compare_runs WHERE (?1 IS NULL OR project_id = ?1) ORDER BY id DESC LIMIT ?2")
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(rusqlite::params![project_id, limit.unwrap_or(50)]).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+        let id: i64 = r.get(0).map_err(|e| e.to_string())?;
+        out.push(CompareRunRow {
+            id,
+            project_id: r.get(1).map_err(|e| e.to_string())?,
+            messages: r.get(2).map_err(|e| e.to_string())?,
+            created_at: r.get(3).map_err(|e| e.to_string())?,
+            results: load_compare_results(&conn, id)?,
+        });
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn get_compare_run(app: tauri::AppHandle, id: i64) -> Result<CompareRunRow, String> {
+    println!("[db.get_compare_run] id={}", id);
+    let conn = ensure_conn(&app)?;
+    let (project_id, messages, created_at) = conn
+        .query_row("SELECT project_id, messages, created_at FROM compare_runs WHERE id = ?", rusqlite::params![id], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+        })
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Comparison {} not found", id),
+            other => other.to_string(),
+        })?;
+    Ok(CompareRunRow { id, project_id, messages, created_at, results: load_compare_results(&conn, id)? })
+}
+
+#[tauri::command]
+pub fn delete_compare_run(app: tauri::AppHandle, id: i64) -> Result<(), String> {
+    println!("[db.delete_compare_run] id={}", id);
+    let conn = ensure_conn(&app)?;
+    // Foreign keys are off by default in SQLite, so results are removed explicitly
+    conn.execute("DELETE FROM compare_results WHERE run_id = ?", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM compare_runs WHERE id = ?", rusqlite::params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// A cached response body if it is younger than `ttl_secs`; marks it as used.
+pub fn response_cache_get(app: &tauri::AppHandle, key: &str, ttl_secs: i64, now: i64) -> Result<Option<String>, String> {
+    let conn = ensure_conn(app)?;
+    let found: Option<(String, i64)> = conn
+        .query_row("SELECT value, created_at FROM response_cache WHERE key = ?", rusqlite::params![key], |r| Ok((r.get(0)?, r.get(1)?)))
+        .map(Some)
+        .or_else(|e| if matches!(e, rusqlite::Error::QueryReturnedNoRows) { Ok(None) } else { Err(e) })
+        .map_err(|e| e.to_string())?;
+    match found {
+        Some((value, created_at)) if now - created_at <= ttl_secs => {
+            conn.execute("UPDATE response_cache SET last_hit_at = ?, hits = hits + 1 WHERE key = ?", rusqlite::params![now, key])
+                .map_err(|e| e.to_string())?;
+            Ok(Some(value))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Stores a response, then evicts expired entries and the least recently used ones
+/// until the cache fits into `max_bytes`.
+pub fn response_cache_put(
+    app: &tauri::AppHandle,
+    key: &str,
+    provider: &str,
+    model: &str,
+    value: &str,
+    ttl_secs: i64,
+    max_bytes: i64,
+    now: i64,
+) -> Result<(), String> {
+    let conn = ensure_conn(app)?;
+    conn.execute(
+        "INSERT INTO response_cache (key, provider, model, value, size, created_at, last_hit_at) VALUES (?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, size = excluded.size, created_at = excluded.created_at, last_hit_at = excluded.last_hit_at",
+        rusqlite::params![key, provider, model, value, value.len() as i64, now, now],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM response_cache WHERE created_at < ?", rusqlite::params![now - ttl_secs])
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM response_cache WHERE key IN (
+             SELECT key FROM (SELECT key, SUM(size) OVER (ORDER BY last_hit_at DESC, created_at DESC, key) AS running FROM response_cache)
+             WHERE running > ?
+         )",
+        rusqlite::params![max_bytes],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Stored (encrypted) gateway token; empty until one is generated.
+pub fn gateway_token(app: &tauri::AppHandle) -> Result<String, String> {
+    let conn = ensure_conn(app)?;
+    conn.query_row("SELECT gateway_token FROM settings WHERE id = 1", [], |r| r.get(0))
+        .map_err(|e| e.to_string())
+}
+
+pub fn set_gateway_token(app: &tauri::AppHandle, stored: &str) -> Result<(), String> {
+    let conn = ensure_conn(app)?;
+    conn.execute("UPDATE settings SET gateway_token = ? WHERE id = 1", rusqlite::params![stored])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Removes every cached response. Returns how many entries were dropped.
+#[tauri::command]
+pub fn clear_response_cache(app: tauri::AppHandle) -> Result<usize, String> {
+    println!("[db.clear_response_cache] Clearing response cache");
+    let conn = ensure_conn(&app)?;
+    conn.execute("DELETE FROM response_cache", []).map_err(|e| e.to_string())
+}
+
+/// Launch profile saved for `model` (a model path, file name or `hf:` reference).
+pub fn load_launch_profile(app: &tauri::AppHandle, model: &str) -> Result<Option<LaunchProfile>, String> {
+    let conn = ensure_conn(app)?;
+    let raw: Option<String> = conn
+        .query_row("SELECT profile FROM llama_launch_profiles WHERE model = ?", rusqlite::params![profile_key(model)], |r| r.get(0))
+        .map(Some)
+        .or_else(|e| if matches!(e, rusqlite::Error::QueryReturnedNoRows) { Ok(None) } else { Err(e) })
+        .map_err(|e| e.to_string())?;
+    raw.map(|raw| serde_json::from_str(&raw).map_err(|e| format!("Stored launch profile is invalid: {}", e)))
+        .transpose()
+}
+
+#[tauri::command]
+pub fn list_launch_profiles(app: tauri::AppHandle) -> Result<Vec<LaunchProfileRow>, String> {
+    println!("[db.list_launch_profiles] Listing launch profiles");
+    let conn = ensure_conn(&app)?;
+    let mut stmt = conn
+        .prepare("SELECT model, profile FROM llama_launch_profiles ORDER BY model")
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+        let model: String = r.get(0).map_err(|e| e.to_string())?;
+        let raw: String = r.get(1).map_err(|e| e.to_string())?;
+        match serde_json::from_str(&raw) {
+            Ok(profile) => out.push(LaunchProfileRow { model, profile }),
+            Err(e) => println!("[db.list_launch_profiles] skipping invalid profile for '{}': {}", model, e),
+        }
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn get_launch_profile(app: tauri::AppHandle, model: String) -> Result<Option<LaunchProfile>, String> {
+    load_launch_profile(&app, &model)
+}
+
+/// Validates and stores the profile; it applies from the next `start_llamacpp_server`.
+#[tauri::command]
+pub fn save_launch_profile(app: tauri::AppHandle, model: String, profile: LaunchProfile) -> Result<(), String> {
+    let key = profile_key(model.trim());
+    println!("[db.save_launch_profile] model='{}'", key);
+    if key.is_empty() {
+        return Err("Model cannot be empty".to_string());
+    }
+    profile.validate()?;
+    let raw = serde_json::to_string(&profile).map_err(|e| e.to_string())?;
+    let conn = ensure_conn(&app)?;
+    conn.execute(
+        "INSERT INTO llama_launch_profiles (model, profile) VALUES (?, ?)
+         ON CONFLICT(model) DO UPDATE SET profile = excluded.profile, updated_at = datetime('now')",
+        rusqlite::params![key, raw],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_launch_profile(app: tauri::AppHandle, model: String) -> Result<(), String> {
+    println!("[db.delete_launch_profile] model='{}'", model);
+    let conn = ensure_conn(&app)?;
+    conn.execute("DELETE FROM llama_launch_profiles WHERE model = ?", rusqlite::params![profile_key(model.trim())])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
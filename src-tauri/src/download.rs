@@ -93,16 +93,8 @@ pub async fn download_server_binaries(
     let temp_ext = if url.ends_with(".zip") { ".zip" } else if url.ends_with(".tgz") || url.ends_with(".tar.gz") { ".tgz" } else if url.ends_with(".dmg") { ".dmg" } else { ".bin" };
     let temp_path = app_dir.join(format!("{}_{}_temp{}", server, variant, temp_ext));
 
-    // Build a robust HTTP client
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0")
-        .connect_timeout(Duration::from_secs(20))
-        .timeout(Duration::from_secs(600)) // overall per-request timeout
-        .tcp_keepalive(Duration::from_secs(30))
-        .pool_idle_timeout(Duration::from_secs(30))
-        .redirect(reqwest::redirect::Policy::limited(10))
-        .build()
-        .map_err(|e| format!("HTTP client creation error: {}", e))?;
+    // Shared client: proxy, CA bundle and timeouts come from settings
+    let client = crate::providers::http::client(&app)?;
 
     // Retry loop with resume support for robustness on large downloads / flaky networks
     let mut attempt: u32 = 0;
@@ -119,7 +111,7 @@ pub async fn download_server_binaries(
         }
 
         // Build request, add Range if we have partial file
-        let mut req = client.get(&url);
+        let mut req = client.get(&url).timeout(Duration::from_secs(600)); // overall per-request timeout
         if existing_size > 0 {
             req = req.header(reqwest::header::RANGE, format!("bytes={}-", existing_size));
         }
@@ -255,6 +247,12 @@ pub async fn download_server_binaries(
                     panic!("Error creating file {}: {}", outpath.display(), e);
                 });
                 let _ = io::copy(&mut file, &mut outfile);
+                // Keep the mode bits of macOS/Linux builds so the binaries stay executable
+                #[cfg(unix)]
+                if let Some(mode) = file.unix_mode() {
+                    use std::os::unix::fs::PermissionsExt;
+                    let _ = fs::set_permissions(&outpath, fs::Permissions::from_mode(mode & 0o777));
+                }
                 outpath
             };
             let _ = outpath;
@@ -285,6 +283,14 @@ pub async fn download_server_binaries(
         let _ = window.emit("binary_download_progress", ProgressPayload { progress: 100, message: "Download completed".into() });
     }
 
+    if server == "llama-cpp" {
+        let binary = crate::utils::llama_server_binary();
+        match crate::utils::find_first_with_names(&target_dir, &[binary], 8) {
+            Some(path) => crate::utils::set_executable(&path)?,
+            None => println!("[download.download_server_binaries] {} not found in {}", binary, target_dir.display()),
+        }
+    }
+
     println!("[download.download_server_binaries] Completed successfully for server='{}'", server);
         Ok("Binaries installed successfully".to_string())
 }
@@ -312,8 +318,7 @@ pub fn check_binary_installed(app: tauri::AppHandle, server: String, variant: St
     let os = std::env::consts::OS;
     match server.as_str() {
         "llama-cpp" => {
-            let candidates: &[&str] = if os == "windows" { &["llama-server.exe"] } else { &["llama-server"] };
-            Ok(find_first_with_names(&base_dir.as_path(), candidates, 5).is_some())
+            Ok(find_first_with_names(&base_dir.as_path(), &[crate::utils::llama_server_binary()], 5).is_some())
         }
         "ollama" => {
             if os == "windows" {
@@ -0,0 +1,516 @@
+use std::convert::Infallible;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::sse::{Event, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::Manager;
+use tokio::sync::{mpsc, oneshot};
+use crate::attachments::ImageAttachment;
+use crate::db::{ProjectRow, SettingsPayload};
+use crate::providers::error::ProviderError;
+use crate::providers::{run_chat, ChatMessage, ChatRequest, ChatResponse};
+use crate::secrets;
+use crate::streaming::{ensure_request_id, StreamEvent, StreamTarget};
+
+const DEFAULT_PORT: u16 = 8765;
+/// Virtual model names of projects: `project/{id}-{slug of the name}`.
+const PROJECT_PREFIX: &str = "project/";
+const PROVIDERS: [&str; 6] = ["openai", "deepseek", "anthropic", "gemini", "ollama", "local"];
+
+/// The running gateway, if any, and the token it accepts.
+#[derive(Default)]
+pub struct GatewayState(pub Mutex<GatewayInfo>);
+
+#[derive(Default)]
+pub struct GatewayInfo {
+    port: Option<u16>,
+    token: String,
+    // Why the last start failed (port in use, ...)
+    error: Option<String>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GatewayStatus {
+    pub running: bool,
+    pub port: Option<u16>,
+    pub base_url: Option<String>,
+    pub token: String,
+    pub error: Option<String>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// The gateway token, generated and stored (encrypted) on first use.
+fn load_token(app: &tauri::AppHandle) -> Result<String, String> {
+    let stored = crate::db::gateway_token(app)?;
+    if !stored.is_empty() {
+        return secrets::decrypt(app, &stored);
+    }
+    let token = secrets::random_token();
+    crate::db::set_gateway_token(app, &secrets::encrypt(app, &token)?)?;
+    Ok(token)
+}
+
+fn status(info: &GatewayInfo) -> GatewayStatus {
+    GatewayStatus {
+        running: info.shutdown.is_some(),
+        port: info.port,
+        base_url: info.port.filter(|_| info.shutdown.is_some()).map(|p| format!("http://127.0.0.1:{}/v1", p)),
+        token: info.token.clone(),
+        error: info.error.clone(),
+    }
+}
+
+/// Starts, stops or restarts the gateway to match `gateway_enabled`/`gateway_port`.
+/// Called at startup and after `save_settings`.
+pub fn apply_settings(app: &tauri::AppHandle) -> Result<(), String> {
+    let settings = crate::db::load_stored_settings(app)?;
+    let enabled = settings.gateway_enabled.unwrap_or(false);
+    let port = settings
+        .gateway_port
+        .and_then(|p| u16::try_from(p).ok())
+        .filter(|p| *p != 0)
+        .unwrap_or(DEFAULT_PORT);
+    let state = app.state::<GatewayState>();
+    let mut info = state.0.lock().map_err(|e| e.to_string())?;
+    if info.shutdown.is_some() && (!enabled || info.port != Some(port)) {
+        if let Some(tx) = info.shutdown.take() {
+            let _ = tx.send(());
+        }
+        println!("[gateway] stopped (port {:?})", info.port);
+        info.port = None;
+    }
+    if !enabled {
+        info.error = None;
+        return Ok(());
+    }
+    if info.shutdown.is_some() {
+        return Ok(());
+    }
+    info.token = load_token(app)?;
+    match serve(app.clone(), port) {
+        Ok(tx) => {
+            info.port = Some(port);
+            info.shutdown = Some(tx);
+            info.error = None;
+            Ok(())
+        }
+        Err(e) => {
+            info.error = Some(e.clone());
+            Err(e)
+        }
+    }
+}
+
+/// Binds 127.0.0.1:`port` (errors such as "address in use" surface here) and serves
+/// until the returned sender fires.
+fn serve(app: tauri::AppHandle, port: u16) -> Result<oneshot::Sender<()>, String> {
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| format!("Cannot listen on 127.0.0.1:{}: {}", port, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let router = Router::new()
+        .route("/v1/models", get(list_models))
+        .route("/v1/chat/completions", post(chat_completions))
+        .with_state(app);
+    let (tx, rx) = oneshot::channel::<()>();
+    tauri::async_runtime::spawn(async move {
+        let listener = match tokio::net::TcpListener::from_std(listener) {
+            Ok(l) => l,
+            Err(e) => {
+                println!("[gateway] listener error: {}", e);
+                return;
+            }
+        };
+        println!("[gateway] listening on http://127.0.0.1:{}", port);
+        let shutdown = async {
+            let _ = rx.await;
+        };
+        if let Err(e) = axum::serve(listener, router).with_graceful_shutdown(shutdown).await {
+            println!("[gateway] server error: {}", e);
+        }
+    });
+    Ok(tx)
+}
+
+#[tauri::command]
+pub fn gateway_status(app: tauri::AppHandle) -> Result<GatewayStatus, String> {
+    let token = load_token(&app)?;
+    let state = app.state::<GatewayState>();
+    let mut info = state.0.lock().map_err(|e| e.to_string())?;
+    info.token = token;
+    Ok(status(&info))
+}
+
+/// Replaces the token; clients using the old one are rejected from the next request on.
+#[tauri::command]
+pub fn regenerate_gateway_token(app: tauri::AppHandle) -> Result<GatewayStatus, String> {
+    println!("[gateway.regenerate_gateway_token] Generating a new token");
+    let token = secrets::random_token();
+    crate::db::set_gateway_token(&app, &secrets::encrypt(&app, &token)?)?;
+    let state = app.state::<GatewayState>();
+    let mut info = state.0.lock().map_err(|e| e.to_string())?;
+    info.token = token;
+    Ok(status(&info))
+}
+
+/// An error body in the OpenAI format.
+fn error(status: StatusCode, kind: &str, message: impl Into<String>) -> Response {
+    (status, Json(json!({"error": {"message": message.into(), "type": kind}}))).into_response()
+}
+
+/// OpenAI error type and HTTP status for a failed generation, so clients can tell
+/// rate limits and oversized prompts from upstream failures.
+fn provider_error_type(e: &ProviderError) -> (StatusCode, &'static str) {
+    match e {
+        ProviderError::RateLimited { .. } => (StatusCode::TOO_MANY_REQUESTS, "rate_limit_error"),
+        ProviderError::ContextLength { .. } | ProviderError::Other { .. } => (StatusCode::BAD_REQUEST, "invalid_request_error"),
+        ProviderError::PromptBlocked { .. } | ProviderError::ResponseBlocked { .. } => (StatusCode::BAD_REQUEST, "content_filter"),
+        _ => (StatusCode::BAD_GATEWAY, "api_error"),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The token clients must send. Loaded lazily, since the llama-server router uses it
+/// while the gateway itself may be off.
+pub(crate) fn token(app: &tauri::AppHandle) -> Result<String, String> {
+    let state = app.state::<GatewayState>();
+    let mut info = state.0.lock().map_err(|e| e.to_string())?;
+    if info.token.is_empty() {
+        info.token = load_token(app)?;
+    }
+    Ok(info.token.clone())
+}
+
+/// Checks `Authorization: Bearer <token>`; also guards the llama-server router.
+pub(crate) fn authorize(app: &tauri::AppHandle, headers: &HeaderMap) -> Result<(), Response> {
+    let expected = token(app).unwrap_or_default();
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("");
+    if expected.is_empty() || !constant_time_eq(given.trim().as_bytes(), expected.as_bytes()) {
+        return Err(error(StatusCode::UNAUTHORIZED, "invalid_request_error", "Invalid or missing bearer token"));
+    }
+    Ok(())
+}
+
+fn slug(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            out.push(c);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    out.trim_matches('-').to_string()
+}
+
+fn project_model_id(p: &ProjectRow) -> String {
+    match slug(&p.name) {
+        s if s.is_empty() => format!("{}{}", PROJECT_PREFIX, p.id),
+        s => format!("{}{}-{}", PROJECT_PREFIX, p.id, s),
+    }
+}
+
+/// `provider/model` for every provider with a model configured in settings.
+fn configured_models(s: &SettingsPayload) -> Vec<(&'static str, String)> {
+    [
+        ("openai", Some(s.api_model.clone())),
+        ("deepseek", Some(s.deepseek_model.clone())),
+        ("anthropic", s.anthropic_model.clone()),
+        ("gemini", s.gemini_model.clone()),
+        ("ollama", s.ollama_model.clone()),
+        ("local", Some(s.model_repo.clone())),
+    ]
+    .into_iter()
+    .filter_map(|(provider, model)| model.filter(|m| !m.trim().is_empty()).map(|m| (provider, m)))
+    .collect()
+}
+
+async fn list_models(State(app): State<tauri::AppHandle>, headers: HeaderMap) -> Response {
+    if let Err(denied) = authorize(&app, &headers) {
+        return denied;
+    }
+    let created = now_secs();
+    let mut data = Vec::new();
+    if let Ok(settings) = crate::db::load_stored_settings(&app) {
+        for (provider, model) in configured_models(&settings) {
+            data.push(json!({"id": format!("{}/{}", provider, model), "object": "model", "created": created, "owned_by": provider}));
+        }
+    }
+    match crate::db::list_projects(app.clone(), None) {
+        Ok(projects) => {
+            for p in &projects {
+                data.push(json!({"id": project_model_id(p), "object": "model", "created": created, "owned_by": "project", "name": p.name}));
+            }
+        }
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, "api_error", e),
+    }
+    Json(json!({"object": "list", "data": data})).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct GatewayMessage {
+    role: String,
+    // A string or OpenAI content parts (`text`, `image_url` with a data: URL)
+    #[serde(default)]
+    content: Value,
+    name: Option<String>,
+    tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionRequest {
+    #[serde(default)]
+    model: String,
+    messages: Vec<GatewayMessage>,
+    #[serde(default)]
+    stream: bool,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    top_k: Option<i64>,
+    min_p: Option<f32>,
+    max_tokens: Option<i64>,
+    max_completion_tokens: Option<i64>,
+    // A string or a list of strings
+    stop: Option<Value>,
+    seed: Option<i64>,
+    response_format: Option<Value>,
+    #[serde(default)]
+    tools: Vec<Value>,
+}
+
+fn to_chat_message(m: GatewayMessage) -> Result<ChatMessage, String> {
+    let mut msg = ChatMessage::new(&m.role, String::new());
+    match m.content {
+        Value::String(text) => msg.content = text,
+        Value::Array(parts) => {
+            let mut images = Vec::new();
+            for part in parts {
+                match part.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => msg.content.push_str(part.get("text").and_then(|t| t.as_str()).unwrap_or("")),
+                    Some("image_url") => {
+                        let url = part.pointer("/image_url/url").and_then(|u| u.as_str()).unwrap_or("");
+                        if !url.starts_with("data:") {
+                            return Err("Only data: URLs are supported for images".to_string());
+                        }
+                        images.push(ImageAttachment { path: None, data: Some(url.to_string()), mime_type: None });
+                    }
+                    _ => {}
+                }
+            }
+            if !images.is_empty() {
+                msg.images = Some(images);
+            }
+        }
+        _ => {}
+    }
+    msg.name = m.name;
+    msg.tool_call_id = m.tool_call_id;
+    Ok(msg)
+}
+
+/// Points the request at a backend: `project/{id}-…` uses the project's endpoint and model
+/// (and bills it to the project), `provider/model` a configured provider, and any other
+/// name the provider selected in settings.
+fn route(app: &tauri::AppHandle, model: &str, req: &mut ChatRequest) -> Result<(), Response> {
+    let unknown = || error(StatusCode::NOT_FOUND, "invalid_request_error", format!("The model '{}' does not exist", model));
+    if let Some(rest) = model.strip_prefix(PROJECT_PREFIX) {
+        let id: i64 = rest.split('-').next().and_then(|n| n.parse().ok()).ok_or_else(unknown)?;
+        let projects = crate::db::list_projects(app.clone(), None)
+            .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, "api_error", e))?;
+        let project = projects.into_iter().find(|p| p.id == id).ok_or_else(unknown)?;
+        req.project_id = Some(project.id);
+        // Projects linked to an endpoint profile are resolved through it by `run_chat`
+        if project.provider_id.is_none() {
+            req.provider = project.provider;
+        }
+        req.model = Some(project.model).filter(|m| !m.trim().is_empty());
+        return Ok(());
+    }
+    match model.split_once('/') {
+        Some((provider, name)) if PROVIDERS.contains(&provider) => {
+            req.provider = provider.to_string();
+            req.model = Some(name.to_string()).filter(|m| !m.trim().is_empty());
+        }
+        _ => {
+            let settings = crate::db::load_stored_settings(app)
+                .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, "api_error", e))?;
+            req.provider = settings.mode;
+            req.model = Some(model.to_string()).filter(|m| !m.trim().is_empty());
+        }
+    }
+    Ok(())
+}
+
+fn build_request(app: &tauri::AppHandle, body: CompletionRequest) -> Result<ChatRequest, Response> {
+    let bad_request = |message: String| error(StatusCode::BAD_REQUEST, "invalid_request_error", message);
+    if !body.tools.is_empty() {
+        return Err(bad_request("`tools` is not supported by this gateway".to_string()));
+    }
+    let messages = body
+        .messages
+        .into_iter()
+        .map(to_chat_message)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(bad_request)?;
+    if messages.is_empty() {
+        return Err(bad_request("`messages` must not be empty".to_string()));
+    }
+    let stop = match body.stop {
+        Some(Value::String(s)) => Some(vec![s]),
+        Some(Value::Array(list)) => Some(list.iter().filter_map(|s| s.as_str().map(|s| s.to_string())).collect()),
+        _ => None,
+    };
+    let response_schema = body
+        .response_format
+        .as_ref()
+        .filter(|f| f.get("type").and_then(|t| t.as_str()) == Some("json_schema"))
+        .and_then(|f| f.pointer("/json_schema/schema"))
+        .cloned();
+    let mut req = ChatRequest {
+        messages,
+        temperature: body.temperature,
+        top_p: body.top_p,
+        top_k: body.top_k,
+        min_p: body.min_p,
+        max_tokens: body.max_completion_tokens.or(body.max_tokens),
+        stop,
+        seed: body.seed,
+        response_schema,
+        stream: Some(body.stream),
+        request_id: Some(ensure_request_id(None)),
+        ..Default::default()
+    };
+    route(app, &body.model, &mut req)?;
+    Ok(req)
+}
+
+fn completion_json(id: &str, model: &str, created: u64, resp: &ChatResponse) -> Value {
+    let mut message = json!({"role": "assistant", "content": resp.content});
+    if let Some(reasoning) = &resp.reasoning {
+        message["reasoning_content"] = json!(reasoning);
+    }
+    json!({
+        "id": id,
+        "object": "chat.completion",
+        "created": created,
+        "model": model,
+        "choices": [{"index": 0, "message": message, "finish_reason": resp.finish_reason}],
+        "usage": resp.usage,
+    })
+}
+
+/// What every streamed chunk repeats.
+#[derive(Clone)]
+struct ChunkHeader {
+    id: String,
+    model: String,
+    created: u64,
+}
+
+impl ChunkHeader {
+    fn event(&self, delta: Value, finish_reason: Option<String>, usage: Option<Value>) -> Event {
+        let mut chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+        });
+        if let Some(usage) = usage {
+            chunk["usage"] = usage;
+        }
+        Event::default().data(chunk.to_string())
+    }
+}
+
+/// Cancels the generation when the HTTP client disconnects before it finished.
+struct CancelOnDrop {
+    app: tauri::AppHandle,
+    request_id: String,
+    finished: bool,
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if !self.finished && crate::generation::cancel(&self.app, &self.request_id) {
+            println!("[gateway] client disconnected, cancelled {}", self.request_id);
+        }
+    }
+}
+
+async fn chat_completions(State(app): State<tauri::AppHandle>, headers: HeaderMap, Json(body): Json<CompletionRequest>) -> Response {
+    if let Err(denied) = authorize(&app, &headers) {
+        return denied;
+    }
+    let model = body.model.clone();
+    println!("[gateway.chat_completions] model='{}', messages_len={}, stream={}", model, body.messages.len(), body.stream);
+    let req = match build_request(&app, body) {
+        Ok(req) => req,
+        Err(rejected) => return rejected,
+    };
+    let request_id = req.request_id.clone().unwrap_or_default();
+    let header = ChunkHeader { id: format!("chatcmpl-{}", request_id), model, created: now_secs() };
+    let (tx, rx) = mpsc::unbounded_channel::<StreamEvent>();
+
+    if !req.stream.unwrap_or(false) {
+        return match run_chat(app, StreamTarget::Channel(tx), req).await {
+            Ok(resp) => Json(completion_json(&header.id, &header.model, header.created, &resp)).into_response(),
+            Err(e) => {
+                let (status, kind) = provider_error_type(&e);
+                error(status, kind, e.to_string())
+            }
+        };
+    }
+
+    let task = tauri::async_runtime::spawn(run_chat(app.clone(), StreamTarget::Channel(tx), req));
+    let guard = CancelOnDrop { app, request_id, finished: false };
+    let first = header.event(json!({"role": "assistant", "content": ""}), None, None);
+    let events = stream::unfold(Some((rx, task, guard, header)), |state| async move {
+        let (mut rx, task, mut guard, header) = state?;
+        let events = match rx.recv().await {
+            Some(StreamEvent::Token(token)) => vec![header.event(json!({"content": token}), None, None)],
+            Some(StreamEvent::Reasoning(token)) => vec![header.event(json!({"reasoning_content": token}), None, None)],
+            // A failure is reported as an error event once the task has returned
+            Some(StreamEvent::Done { error: Some(_), .. }) => Vec::new(),
+            Some(StreamEvent::Done { finish_reason, usage, error: None }) => vec![header.event(json!({}), finish_reason, usage)],
+            // The sink is dropped once the generation returned
+            None => {
+                guard.finished = true;
+                let mut last = Vec::new();
+                let failure = match task.await {
+                    Ok(Err(e)) => Some(e),
+                    Err(e) => Some(ProviderError::Other { message: e.to_string() }),
+                    Ok(Ok(_)) => None,
+                };
+                if let Some(e) = failure {
+                    let (_, kind) = provider_error_type(&e);
+                    last.push(Event::default().data(json!({"error": {"message": e.to_string(), "type": kind}}).to_string()));
+                }
+                last.push(Event::default().data("[DONE]"));
+                return Some((last, None));
+            }
+        };
+        Some((events, Some((rx, task, guard, header))))
+    });
+    let events = stream::iter(vec![first])
+        .chain(events.flat_map(stream::iter))
+        .map(Ok::<_, Infallible>);
+    Sse::new(events).into_response()
+}
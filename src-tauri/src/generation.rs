@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use futures::future::{AbortHandle, Abortable};
+use tauri::Manager;
+use crate::providers::error::ProviderError;
+
+/// Abort handles of in-flight generations keyed by request id.
+#[derive(Default)]
+pub struct GenerationState(pub Mutex<HashMap<String, AbortHandle>>);
+
+/// Runs a generation future under `request_id` so it can be aborted from `cancel_generation`.
+/// Aborting drops the future together with its HTTP connection. llama-server and Ollama
+/// requests are always streamed, since they stop decoding only when a streaming client disconnects.
+/// A request id that is already running is rejected, so one generation never drops another's handle.
+pub async fn run_cancellable<T, F>(app: &tauri::AppHandle, request_id: &str, fut: F) -> Result<T, ProviderError>
+where
+    F: Future<Output = Result<T, ProviderError>>,
+{
+    let (handle, registration) = AbortHandle::new_pair();
+    {
+        let state = app.state::<GenerationState>();
+        let mut map = state.0.lock().map_err(|_| "lock poisoned")?;
+        if map.contains_key(request_id) {
+            return Err(format!("request_id '{}' is already running", request_id).into());
+        }
+        map.insert(request_id.to_string(), handle);
+    }
+    let result = Abortable::new(fut, registration).await;
+    if let Ok(mut map) = app.state::<GenerationState>().0.lock() {
+        map.remove(request_id);
+    }
+    result.unwrap_or(Err(ProviderError::Cancelled))
+}
+
+fn abort(state: &GenerationState, request_id: &str) -> Result<bool, String> {
+    let handle = state.0.lock().map_err(|_| "lock poisoned")?.remove(request_id);
+    match handle {
+        Some(h) => {
+            h.abort();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Aborts a running generation from backend code (e.g. when an HTTP client disconnects).
+pub fn cancel(app: &tauri::AppHandle, request_id: &str) -> bool {
+    abort(&app.state::<GenerationState>(), request_id).unwrap_or(false)
+}
+
+#[tauri::command]
+pub fn cancel_generation(state: tauri::State<GenerationState>, request_id: String) -> Result<bool, String> {
+    println!("[generation.cancel_generation] request_id='{}'", request_id);
+    abort(&state, &request_id)
+}
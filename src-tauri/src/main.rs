@@ -3,11 +3,21 @@
 mod utils;
 mod download;
 mod api;
+mod streaming;
+mod generation;
 mod db;
 mod backends;
 mod context;
 mod system;
 mod plugins;
+mod providers;
+mod tools;
+mod schema;
+mod attachments;
+mod secrets;
+mod cache;
+mod reasoning;
+mod gateway;
 
 use tauri::Manager;
 use crate::plugins::PluginManager;
@@ -28,11 +38,20 @@ pub fn main() {
         .invoke_handler(tauri::generate_handler![
             api::query_deepseek,
             api::query_openai,
+            providers::chat,
+            providers::embeddings::embed,
+            providers::models::list_openai_models,
+            providers::compare::compare_models,
+            generation::cancel_generation,
+            tools::tools_list,
+            tools::tools_submit_result,
             backends::llama_cpp::query::query_llamacpp,
             download::download_llama_binaries,
             download::download_server_binaries,
             backends::llama_cpp::server::start_llamacpp_server,
             backends::llama_cpp::server::stop_llamacpp_server,
+            backends::llama_cpp::status::llamacpp_server_status,
+            backends::llama_cpp::router::llamacpp_router_status,
             backends::llama_cpp::download::download_model_file,
             backends::llama_cpp::models::model_exists,
             backends::llama_cpp::models::resolve_model_path,
@@ -46,6 +65,24 @@ pub fn main() {
             db::delete_project,
             db::save_project_prompt,
             db::list_project_prompts,
+            db::list_model_prices,
+            db::save_model_price,
+            db::delete_model_price,
+            db::usage_summary,
+            db::list_provider_profiles,
+            db::save_provider_profile,
+            db::delete_provider_profile,
+            db::set_project_provider,
+            db::list_compare_runs,
+            db::get_compare_run,
+            db::delete_compare_run,
+            db::clear_response_cache,
+            db::list_launch_profiles,
+            db::get_launch_profile,
+            db::save_launch_profile,
+            db::delete_launch_profile,
+            gateway::gateway_status,
+            gateway::regenerate_gateway_token,
             backends::ollama::query::query_ollama,
             backends::ollama::models::list_ollama_models,
             backends::ollama::server::start_ollama_server,
@@ -73,6 +110,19 @@ pub fn main() {
                     .with_memory(MemoryRefreshKind::everything()),
             );
             app.manage(crate::system::SystemState(std::sync::Mutex::new(sys)));
+            app.manage(crate::generation::GenerationState::default());
+            app.manage(crate::tools::ToolState::default());
+            app.manage(crate::providers::http::HttpClientState::default());
+            app.manage(crate::providers::models::ModelCacheState::default());
+            app.manage(crate::gateway::GatewayState::default());
+            app.manage(crate::backends::llama_cpp::LlamaServerState::default());
+            app.manage(crate::backends::llama_cpp::router::RouterState::default());
+            if let Err(e) = crate::gateway::apply_settings(&handle) {
+                println!("Warning: Failed to start the API gateway: {}", e);
+            }
+            if let Err(e) = crate::backends::llama_cpp::router::apply_settings(&handle) {
+                println!("Warning: Failed to start the llama.cpp router: {}", e);
+            }
 
             let plugins_dir = app.path()
                 .app_data_dir()
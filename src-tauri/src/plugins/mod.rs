@@ -20,6 +20,9 @@ pub struct BackendMeta {
     pub entry: Option<String>,
     #[serde(default)]
     pub permissions: Vec<String>,
+    // JSON-schema tools the plugin exposes to chat models; handled by its frontend
+    #[serde(default)]
+    pub tools: Vec<crate::tools::ToolDefinition>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -0,0 +1,251 @@
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use crate::db::SettingsPayload;
+use crate::streaming::{SseDecoder, StreamSink};
+use super::error::ProviderError;
+use super::http::{send_with_retry, with_headers};
+use super::{fill_if_empty, ChatRequest, ChatResponse, Provider, Usage};
+
+const DEFAULT_BASE: &str = "https://api.anthropic.com";
+const DEFAULT_MODEL: &str = "claude-3-5-haiku-latest";
+const API_VERSION: &str = "2023-06-01";
+// The Messages API requires max_tokens; used when settings say "unlimited"
+const DEFAULT_MAX_TOKENS: i64 = 4096;
+
+/// Anthropic Messages API (`/v1/messages`).
+pub struct AnthropicProvider;
+
+impl Provider for AnthropicProvider {
+    fn apply_defaults(&self, req: &mut ChatRequest, settings: &SettingsPayload) {
+        fill_if_empty(&mut req.base_url, settings.anthropic_base.as_deref().unwrap_or(""));
+        fill_if_empty(&mut req.api_key, settings.anthropic_key.as_deref().unwrap_or(""));
+        fill_if_empty(&mut req.model, settings.anthropic_model.as_deref().unwrap_or(""));
+    }
+
+    fn chat<'a>(
+        &'a self,
+        req: &'a ChatRequest,
+        sink: Option<&'a StreamSink>,
+    ) -> BoxFuture<'a, Result<ChatResponse, ProviderError>> {
+        Box::pin(async move {
+            let base = req
+                .base_url
+                .as_deref()
+                .map(|s| s.trim().trim_end_matches('/'))
+                .filter(|s| !s.is_empty())
+                .unwrap_or(DEFAULT_BASE);
+            let url = if base.ends_with("/v1") {
+                format!("{}/messages", base)
+            } else {
+                format!("{}/v1/messages", base)
+            };
+            let model = req
+                .model
+                .clone()
+                .filter(|m| !m.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+            let body = build_body(req, &model, sink.is_some());
+
+            let client = req.http_client();
+            let response = send_with_retry(|| {
+                let builder = client
+                    .post(&url)
+                    .header("x-api-key", req.api_key.as_deref().unwrap_or(""))
+                    .header("anthropic-version", API_VERSION)
+                    .json(&body);
+                with_headers(builder, req.extra_headers.as_ref())
+            })
+            .await?;
+
+            match sink {
+                Some(sink) => read_stream(response, sink, &model).await,
+                None => {
+                    let v: Value = response.json().await?;
+                    Ok(parse_message(&v, &model))
+                }
+            }
+        })
+    }
+}
+
+fn build_body(req: &ChatRequest, model: &str, stream: bool) -> Value {
+    // System prompts go to the top-level `system` field, not into `messages`
+    let mut system: Vec<String> = req
+        .messages
+        .iter()
+        .filter(|m| m.role == "system")
+        .map(|m| m.content.clone())
+        .collect();
+    // No native schema option: describe it in the system prompt
+    if let Some(schema) = &req.response_schema { system.push(crate::schema::instruction(schema)); }
+    let messages: Vec<Value> = req
+        .messages
+        .iter()
+        .filter(|m| m.role != "system")
+        .map(|m| {
+            if m.image_list().is_empty() {
+                return json!({"role": m.role, "content": m.content});
+            }
+            let mut blocks: Vec<Value> = m
+                .image_list()
+                .iter()
+                .map(|i| json!({"type": "image", "source": {"type": "base64", "media_type": i.mime(), "data": i.base64()}}))
+                .collect();
+            blocks.push(json!({"type": "text", "text": m.content}));
+            json!({"role": m.role, "content": blocks})
+        })
+        .collect();
+
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "max_tokens": req.max_tokens.filter(|n| *n > 0).unwrap_or(DEFAULT_MAX_TOKENS),
+    });
+    if !system.is_empty() { body["system"] = json!(system.join("\n\n")); }
+    if let Some(t) = req.temperature { body["temperature"] = json!(t); }
+    if let Some(p) = req.top_p { body["top_p"] = json!(p); }
+    if let Some(k) = req.top_k.filter(|k| *k > 0) { body["top_k"] = json!(k); }
+    if let Some(stop) = req.stop.as_ref().filter(|s| !s.is_empty()) { body["stop_sequences"] = json!(stop); }
+    if stream { body["stream"] = json!(true); }
+    if let (Some(Value::Object(extra)), Some(map)) = (req.extra.as_ref(), body.as_object_mut()) {
+        for (k, v) in extra {
+            map.insert(k.clone(), v.clone());
+        }
+    }
+    body
+}
+
+/// Maps Anthropic stop reasons onto the OpenAI-style values used by `ChatResponse`.
+fn map_stop_reason(reason: &str) -> String {
+    match reason {
+        "end_turn" | "stop_sequence" => "stop".to_string(),
+        "max_tokens" => "length".to_string(),
+        "tool_use" => "tool_calls".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn parse_usage(v: &Value) -> Option<Usage> {
+    let prompt_tokens = v.get("input_tokens").and_then(|x| x.as_u64());
+    let completion_tokens = v.get("output_tokens").and_then(|x| x.as_u64());
+    if prompt_tokens.is_none() && completion_tokens.is_none() { return None; }
+    Some(Usage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: Some(prompt_tokens.unwrap_or(0) + completion_tokens.unwrap_or(0)),
+    })
+}
+
+fn parse_message(v: &Value, model: &str) -> ChatResponse {
+    // Text blocks form the answer and `thinking` blocks the reasoning; other block types are skipped
+    let blocks = |kind: &str, field: &str| {
+        v.get("content")
+            .and_then(|c| c.as_array())
+            .map(|blocks| {
+                blocks
+                    .iter()
+                    .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some(kind))
+                    .filter_map(|b| b.get(field).and_then(|t| t.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("")
+            })
+            .unwrap_or_default()
+    };
+    let content = blocks("text", "text");
+    let reasoning = blocks("thinking", "thinking");
+    ChatResponse {
+        content,
+        role: v.get("role").and_then(|r| r.as_str()).unwrap_or("assistant").to_string(),
+        finish_reason: v.get("stop_reason").and_then(|r| r.as_str()).map(map_stop_reason),
+        usage: v.get("usage").and_then(parse_usage),
+        model: v.get("model").and_then(|m| m.as_str()).unwrap_or(model).to_string(),
+        latency_ms: 0,
+        tool_calls: None,
+        reasoning: Some(reasoning).filter(|r| !r.is_empty()),
+        parsed: None,
+        cached: false,
+    }
+}
+
+/// Reads the Messages API event stream: `message_start`, `content_block_delta`,
+/// `message_delta` and `message_stop`, plus `error` events.
+async fn read_stream(response: reqwest::Response, sink: &StreamSink, model: &str) -> Result<ChatResponse, ProviderError> {
+    let mut decoder = SseDecoder::default();
+    let mut resp = ChatResponse {
+        content: String::new(),
+        role: "assistant".to_string(),
+        finish_reason: None,
+        usage: None,
+        model: model.to_string(),
+        latency_ms: 0,
+        tool_calls: None,
+        reasoning: None,
+        parsed: None,
+        cached: false,
+    };
+    let mut input_tokens: Option<u64> = None;
+    let mut output_tokens: Option<u64> = None;
+    let mut reasoning = String::new();
+    let mut error: Option<ProviderError> = None;
+
+    let mut handle_data = |data: &str| -> bool {
+        let Ok(v) = serde_json::from_str::<Value>(data) else { return false; };
+        match v.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+            "message_start" => {
+                if let Some(m) = v.pointer("/message/model").and_then(|m| m.as_str()) {
+                    resp.model = m.to_string();
+                }
+                input_tokens = v.pointer("/message/usage/input_tokens").and_then(|x| x.as_u64());
+            }
+            "content_block_delta" => {
+                if let Some(text) = v.pointer("/delta/text").and_then(|t| t.as_str()) {
+                    resp.content.push_str(text);
+                    sink.token(text);
+                }
+                // `thinking_delta` of an extended-thinking block
+                if let Some(text) = v.pointer("/delta/thinking").and_then(|t| t.as_str()) {
+                    reasoning.push_str(text);
+                    sink.reasoning(text);
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = v.pointer("/delta/stop_reason").and_then(|r| r.as_str()) {
+                    resp.finish_reason = Some(map_stop_reason(reason));
+                }
+                if let Some(n) = v.pointer("/usage/output_tokens").and_then(|x| x.as_u64()) {
+                    output_tokens = Some(n);
+                }
+            }
+            "message_stop" => return true,
+            "error" => {
+                error = Some(ProviderError::from_stream_error(&v));
+                return true;
+            }
+            _ => {}
+        }
+        false
+    };
+
+    let mut stream = response.bytes_stream();
+    let mut done = false;
+    while let Some(item) = stream.next().await {
+        let chunk = item?;
+        for data in decoder.push(&chunk) {
+            if handle_data(&data) { done = true; break; }
+        }
+        if done { break; }
+    }
+    if !done {
+        if let Some(data) = decoder.finish() {
+            handle_data(&data);
+        }
+    }
+
+    if let Some(e) = error {
+        return Err(e);
+    }
+    resp.usage = parse_usage(&json!({"input_tokens": input_tokens, "output_tokens": output_tokens}));
+    resp.reasoning = Some(reasoning).filter(|r| !r.is_empty());
+    Ok(resp)
+}
@@ -0,0 +1,155 @@
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use tokio::task::JoinSet;
+use crate::db::{CompareResultRow, CompareRunRow};
+use crate::streaming::{ensure_request_id, StreamTarget};
+use super::{run_chat, ChatMessage, ChatRequest};
+
+/// Event emitted as soon as one target of a comparison finishes.
+pub const COMPARE_RESULT_EVENT: &str = "compare_result";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompareRequest {
+    // Either a message list or a single prompt
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
+    pub prompt: Option<String>,
+    // One chat request per target; empty `messages` take the shared ones
+    pub targets: Vec<ChatRequest>,
+    pub stream: Option<bool>,
+    // Comparison id; target `i` streams and is cancellable as "{request_id}:{i}"
+    pub request_id: Option<String>,
+    pub project_id: Option<i64>,
+}
+
+#[derive(Clone, Serialize)]
+struct CompareResultPayload {
+    request_id: String,
+    result: CompareResultRow,
+}
+
+/// "provider/model" shown for a target before it has run.
+fn target_label(target: &ChatRequest) -> (String, String) {
+    let provider = match (target.provider.trim(), target.profile_id) {
+        ("", Some(id)) => format!("profile:{}", id),
+        (p, _) => p.to_string(),
+    };
+    (provider, target.model.clone().unwrap_or_default())
+}
+
+/// Stored copy of the shared messages; inline image data is dropped to keep the table small.
+fn messages_for_storage(messages: &[ChatMessage]) -> String {
+    let mut stored = messages.to_vec();
+    for m in stored.iter_mut() {
+        if let Some(images) = m.images.as_mut() {
+            for image in images.iter_mut() {
+                image.data = None;
+            }
+        }
+    }
+    serde_json::to_string(&stored).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Sends the same conversation to every target concurrently. A failing target does not
+/// affect the others: its error is recorded in its result. Results are emitted as
+/// `compare_result` events while they arrive, then stored and returned in target order.
+#[tauri::command]
+pub async fn compare_models(app: tauri::AppHandle, window: tauri::Window, request: CompareRequest) -> Result<CompareRunRow, String> {
+    println!("[providers.compare_models] targets={}, messages_len={}, stream={:?}", request.targets.len(), request.messages.len(), request.stream);
+    if request.targets.is_empty() {
+        return Err("targets cannot be empty".to_string());
+    }
+    let messages = match (request.messages.is_empty(), request.prompt.as_deref().map(str::trim)) {
+        (false, _) => request.messages,
+        (true, Some(prompt)) if !prompt.is_empty() => vec![ChatMessage::new("user", prompt.to_string())],
+        _ if request.targets.iter().all(|t| !t.messages.is_empty()) => Vec::new(),
+        _ => return Err("Either messages or prompt is required".to_string()),
+    };
+    let compare_id = ensure_request_id(request.request_id);
+
+    let mut tasks = JoinSet::new();
+    let mut labels = Vec::new();
+    for (index, mut target) in request.targets.into_iter().enumerate() {
+        if target.messages.is_empty() {
+            target.messages = messages.clone();
+        }
+        target.stream = target.stream.or(request.stream);
+        target.project_id = target.project_id.or(request.project_id);
+        let target_id = format!("{}:{}", compare_id, index);
+        target.request_id = Some(target_id.clone());
+        let (provider, model) = target_label(&target);
+        labels.push((provider.clone(), model.clone(), target_id.clone()));
+        let (app, window, compare_id) = (app.clone(), window.clone(), compare_id.clone());
+
+        tasks.spawn(async move {
+            let started = Instant::now();
+            let outcome = run_chat(app, StreamTarget::Window(window.clone()), target).await;
+            let latency_ms = started.elapsed().as_millis() as i64;
+            let result = match outcome {
+                Ok(resp) => {
+                    let usage = resp.usage.clone().unwrap_or_default();
+                    CompareResultRow {
+                        target_index: index as i64,
+                        provider,
+                        model: if model.is_empty() { resp.model } else { model },
+                        request_id: target_id,
+                        content: Some(resp.content),
+                        error: None,
+                        finish_reason: resp.finish_reason,
+                        latency_ms: resp.latency_ms as i64,
+                        prompt_tokens: usage.prompt_tokens.map(|n| n as i64),
+                        completion_tokens: usage.completion_tokens.map(|n| n as i64),
+                        total_tokens: usage.total_tokens.map(|n| n as i64),
+                    }
+                }
+                Err(e) => CompareResultRow {
+                    target_index: index as i64,
+                    provider,
+                    model,
+                    request_id: target_id,
+                    content: None,
+                    error: Some(e.to_string()),
+                    finish_reason: None,
+                    latency_ms,
+                    prompt_tokens: None,
+                    completion_tokens: None,
+                    total_tokens: None,
+                },
+            };
+            let _ = window.emit(COMPARE_RESULT_EVENT, CompareResultPayload { request_id: compare_id, result: result.clone() });
+            result
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => println!("[providers.compare_models] target task failed: {}", e),
+        }
+    }
+    // A panicking target still gets a result row
+    for (index, (provider, model, request_id)) in labels.into_iter().enumerate() {
+        if !results.iter().any(|r| r.target_index == index as i64) {
+            results.push(CompareResultRow {
+                target_index: index as i64,
+                provider,
+                model,
+                request_id,
+                content: None,
+                error: Some("Target failed unexpectedly".to_string()),
+                finish_reason: None,
+                latency_ms: 0,
+                prompt_tokens: None,
+                completion_tokens: None,
+                total_tokens: None,
+            });
+        }
+    }
+    results.sort_by_key(|r| r.target_index);
+
+    let stored = messages_for_storage(&messages);
+    let id = crate::db::save_compare_run(&app, request.project_id, &stored, &results)?;
+    crate::db::get_compare_run(app, id)
+}
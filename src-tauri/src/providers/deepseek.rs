@@ -0,0 +1,49 @@
+use futures::future::BoxFuture;
+use serde_json::json;
+use crate::db::SettingsPayload;
+use crate::streaming::StreamSink;
+use super::error::ProviderError;
+use super::{fill_if_empty, openai, ChatRequest, ChatResponse, Provider};
+
+const DEFAULT_URL: &str = "https://api.deepseek.com/chat/completions";
+const DEFAULT_MODEL: &str = "deepseek-chat";
+
+/// DeepSeek cloud API. Speaks the OpenAI protocol, but `base_url` is the full completions URL.
+pub struct DeepSeekProvider;
+
+impl Provider for DeepSeekProvider {
+    fn apply_defaults(&self, req: &mut ChatRequest, settings: &SettingsPayload) {
+        fill_if_empty(&mut req.base_url, &settings.deepseek_url);
+        fill_if_empty(&mut req.api_key, &settings.api_key);
+        fill_if_empty(&mut req.model, &settings.deepseek_model);
+    }
+
+    fn chat<'a>(
+        &'a self,
+        req: &'a ChatRequest,
+        sink: Option<&'a StreamSink>,
+    ) -> BoxFuture<'a, Result<ChatResponse, ProviderError>> {
+        Box::pin(async move {
+            let url = req
+                .base_url
+                .as_deref()
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .unwrap_or(DEFAULT_URL);
+            let model = req
+                .model
+                .clone()
+                .filter(|m| !m.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+            let mut body = openai::build_body(req, &model, sink.is_some());
+            // DeepSeek has JSON mode but no schema enforcement: describe the schema in a system message
+            if let Some(schema) = &req.response_schema {
+                body["response_format"] = json!({"type": "json_object"});
+                if let Some(messages) = body["messages"].as_array_mut() {
+                    messages.insert(0, json!({"role": "system", "content": crate::schema::instruction(schema)}));
+                }
+            }
+            openai::send(&req.http_client(), url, req.api_key.as_deref(), req.extra_headers.as_ref(), body, sink, &model).await
+        })
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use super::error::ProviderError;
+use super::http::{send_with_retry, with_headers};
+use super::{fill_if_empty, load_profile, local_port, record_usage, request_settings, unmask, Usage};
+
+/// Inputs sent per HTTP request.
+const DEFAULT_BATCH_SIZE: usize = 32;
+const OPENAI_DEFAULT_BASE: &str = "https://api.openai.com/v1";
+const OPENAI_DEFAULT_MODEL: &str = "text-embedding-3-small";
+const OLLAMA_DEFAULT_BASE: &str = "http://127.0.0.1:11434";
+const OLLAMA_DEFAULT_MODEL: &str = "nomic-embed-text";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    // One vector per input text, in input order
+    pub embeddings: Vec<Vec<f32>>,
+    pub dimension: usize,
+    pub model: String,
+    pub usage: Option<Usage>,
+}
+
+enum Backend {
+    OpenAi { url: String, api_key: Option<String>, headers: Option<HashMap<String, String>> },
+    Ollama { url: String, headers: Option<HashMap<String, String>> },
+    LlamaCpp { url: String },
+}
+
+/// Parses `[f32, ...]`.
+fn vector(v: &Value) -> Option<Vec<f32>> {
+    v.as_array()?.iter().map(|x| x.as_f64().map(|f| f as f32)).collect()
+}
+
+/// llama-server returns either one pooled vector or one vector per token
+/// (when started without pooling); per-token output is mean-pooled.
+fn llama_vector(v: &Value) -> Option<Vec<f32>> {
+    if let Some(flat) = vector(v) {
+        return Some(flat);
+    }
+    let rows: Vec<Vec<f32>> = v.as_array()?.iter().map(vector).collect::<Option<_>>()?;
+    let first = rows.first()?;
+    let mut mean = vec![0f32; first.len()];
+    for row in &rows {
+        for (m, x) in mean.iter_mut().zip(row) {
+            *m += x / rows.len() as f32;
+        }
+    }
+    Some(mean)
+}
+
+fn invalid(message: &str) -> ProviderError {
+    ProviderError::InvalidResponse { message: message.to_string() }
+}
+
+async fn embed_batch(
+    client: &reqwest::Client,
+    backend: &Backend,
+    model: &str,
+    batch: &[String],
+) -> Result<(Vec<Vec<f32>>, Option<Usage>), ProviderError> {
+    match backend {
+        Backend::OpenAi { url, api_key, headers } => {
+            let body = json!({"model": model, "input": batch});
+            let response = send_with_retry(|| {
+                let mut req = with_headers(client.post(url).json(&body), headers.as_ref());
+                if let Some(key) = api_key.as_deref().filter(|k| !k.trim().is_empty()) {
+                    req = req.header("Authorization", format!("Bearer {}", key));
+                }
+                req
+            })
+            .await?;
+            let v: Value = response.json().await?;
+            let mut items: Vec<&Value> = v
+                .get("data")
+                .and_then(|d| d.as_array())
+                .ok_or_else(|| invalid("missing `data` in embeddings response"))?
+                .iter()
+                .collect();
+            items.sort_by_key(|item| item.get("index").and_then(|i| i.as_u64()).unwrap_or(0));
+            let vectors = items
+                .iter()
+                .map(|item| item.get("embedding").and_then(vector))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("malformed embedding vector"))?;
+            Ok((vectors, v.get("usage").and_then(Usage::from_openai)))
+        }
+        Backend::Ollama { url, headers } => {
+            let body = json!({"model": model, "input": batch});
+            let response = send_with_retry(|| with_headers(client.post(url).json(&body), headers.as_ref())).await?;
+            let v: Value = response.json().await?;
+            let vectors = v
+                .get("embeddings")
+                .and_then(|e| e.as_array())
+                .ok_or_else(|| invalid("missing `embeddings` in Ollama response"))?
+                .iter()
+                .map(vector)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("malformed embedding vector"))?;
+            let usage = v.get("prompt_eval_count").and_then(|x| x.as_u64()).map(|n| Usage {
+                prompt_tokens: Some(n),
+                completion_tokens: None,
+                total_tokens: Some(n),
+            });
+            Ok((vectors, usage))
+        }
+        Backend::LlamaCpp { url } => {
+            let body = json!({"content": batch});
+            let response = send_with_retry(|| client.post(url).json(&body)).await?;
+            let v: Value = response.json().await?;
+            // Either [{index, embedding}, ...] or a single {embedding} object
+            let mut items: Vec<&Value> = match &v {
+                Value::Array(list) => list.iter().collect(),
+                other => vec![other],
+            };
+            items.sort_by_key(|item| item.get("index").and_then(|i| i.as_u64()).unwrap_or(0));
+            let vectors = items
+                .iter()
+                .map(|item| item.get("embedding").and_then(llama_vector))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("malformed embedding vector (is llama-server started with --embeddings?)"))?;
+            Ok((vectors, None))
+        }
+    }
+}
+
+/// Embeds `texts` with the given backend, sending them in batches.
+/// Empty endpoint/model arguments are taken from the provider profile, then settings or sensible defaults.
+#[tauri::command]
+pub async fn embed(
+    app: tauri::AppHandle,
+    texts: Vec<String>,
+    provider: Option<String>,
+    profile_id: Option<i64>,
+    model: Option<String>,
+    base_url: Option<String>,
+    api_key: Option<String>,
+    port: Option<u16>,
+    batch_size: Option<usize>,
+    project_id: Option<i64>,
+) -> Result<EmbeddingResponse, ProviderError> {
+    println!("[providers.embed] provider={:?}, profile_id={:?}, model={:?}, texts={}", provider, profile_id, model, texts.len());
+    if texts.is_empty() {
+        return Err("texts cannot be empty".into());
+    }
+    let settings = request_settings(&app).ok();
+    let mut model = model;
+    let mut base_url = base_url;
+    let mut api_key = api_key;
+    unmask(&mut api_key);
+    let mut port = port;
+    let mut provider = provider.unwrap_or_default();
+    let mut headers = None;
+    if let Some(id) = profile_id {
+        let profile = load_profile(&app, id)?;
+        if provider.trim().is_empty() {
+            provider = profile.kind.clone();
+        }
+        if profile.kind == "local" {
+            port = port.or_else(|| local_port(&profile.base_url));
+        } else {
+            fill_if_empty(&mut base_url, &profile.base_url);
+        }
+        fill_if_empty(&mut api_key, &profile.api_key);
+        fill_if_empty(&mut model, &profile.default_model);
+        headers = Some(profile.extra_headers).filter(|h| !h.is_empty());
+    }
+
+    let kind = provider.trim().to_lowercase();
+    let backend = match kind.as_str() {
+        "openai" => {
+            if let Some(s) = &settings {
+                fill_if_empty(&mut base_url, &s.api_base);
+                fill_if_empty(&mut api_key, &s.api_key);
+            }
+            fill_if_empty(&mut model, OPENAI_DEFAULT_MODEL);
+            let base = base_url.as_deref().unwrap_or(OPENAI_DEFAULT_BASE).trim_end_matches('/');
+            Backend::OpenAi { url: format!("{}/embeddings", base), api_key, headers }
+        }
+        "ollama" => {
+            if let Some(s) = &settings {
+                fill_if_empty(&mut base_url, s.ollama_base.as_deref().unwrap_or(""));
+            }
+            fill_if_empty(&mut model, OLLAMA_DEFAULT_MODEL);
+            let base = base_url.as_deref().unwrap_or(OLLAMA_DEFAULT_BASE).trim_end_matches('/');
+            Backend::Ollama { url: format!("{}/api/embed", base), headers }
+        }
+        "local" | "llamacpp" | "llama-cpp" => {
+            let port = port
+                .or_else(|| settings.as_ref().and_then(|s| s.server_port).and_then(|p| u16::try_from(p).ok()))
+                .unwrap_or(8080);
+            if let Some(s) = &settings {
+                fill_if_empty(&mut model, &s.model_repo);
+            }
+            // The llama-server router serves only the OpenAI route and wants the gateway token
+            match crate::backends::llama_cpp::router::auth_token(&app, port) {
+                Some(token) => Backend::OpenAi { url: format!("http://127.0.0.1:{}/v1/embeddings", port), api_key: Some(token), headers },
+                None => Backend::LlamaCpp { url: format!("http://127.0.0.1:{}/embedding", port) },
+            }
+        }
+        other => return Err(format!("Embeddings are not supported for provider '{}'", other).into()),
+    };
+    let model = model.unwrap_or_else(|| "local-model".to_string());
+
+    let started = Instant::now();
+    let client = super::http::client(&app)?;
+    let mut embeddings: Vec<Vec<f32>> = Vec::with_capacity(texts.len());
+    let mut usage: Option<Usage> = None;
+    for batch in texts.chunks(batch_size.unwrap_or(DEFAULT_BATCH_SIZE).max(1)) {
+        let (vectors, batch_usage) = embed_batch(&client, &backend, &model, batch).await?;
+        if vectors.len() != batch.len() {
+            return Err(invalid(&format!("expected {} embeddings, got {}", batch.len(), vectors.len())));
+        }
+        embeddings.extend(vectors);
+        usage = Usage::add(usage, batch_usage);
+    }
+
+    let dimension = embeddings.first().map(|e| e.len()).unwrap_or(0);
+    if embeddings.iter().any(|e| e.len() != dimension) {
+        return Err(invalid("embeddings have inconsistent dimensions"));
+    }
+    record_usage(&app, project_id, &kind, &model, usage.as_ref(), started.elapsed().as_millis() as u64);
+    Ok(EmbeddingResponse { embeddings, dimension, model, usage })
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+use serde::Serialize;
+use serde_json::Value;
+use crate::schema::StructuredOutputError;
+
+/// Longest part of an error body kept in messages.
+const MAX_MESSAGE_LEN: usize = 500;
+
+/// Failure of a provider request, classified so callers can tell what went wrong.
+/// Generation commands return it as is; the frontend gets `{ kind, ... }`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProviderError {
+    /// Missing, invalid or unauthorized API key (HTTP 401/403).
+    Auth { status: u16, message: String },
+    /// HTTP 429. `retry_after` is in seconds when the server said when to come back.
+    RateLimited { message: String, retry_after: Option<u64> },
+    /// The prompt (plus requested output) does not fit the model context.
+    ContextLength { message: String },
+    /// HTTP 5xx or an "overloaded" reply.
+    Server { status: u16, message: String },
+    /// The server could not be reached or the connection broke.
+    Network { message: String },
+    /// Any other non-success status.
+    Http { status: u16, message: String },
+    /// The server answered but the body could not be understood.
+    InvalidResponse { message: String },
+    /// The prompt itself was rejected by a safety filter.
+    PromptBlocked { reason: String },
+    /// Generation was stopped by a filter before any text was produced.
+    ResponseBlocked { reason: String, categories: Vec<String> },
+    /// The answer did not satisfy `response_schema`, repair attempts included.
+    StructuredOutput { error: StructuredOutputError },
+    /// Stopped through `cancel_generation`.
+    Cancelled,
+    /// Failed before or after talking to the provider (bad arguments, settings, storage).
+    Other { message: String },
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderError::Auth { status, message } => write!(f, "Authentication failed (HTTP {}): {}", status, message),
+            ProviderError::RateLimited { message, retry_after: Some(secs) } => {
+                write!(f, "Rate limited (HTTP 429, retry after {}s): {}", secs, message)
+            }
+            ProviderError::RateLimited { message, retry_after: None } => write!(f, "Rate limited (HTTP 429): {}", message),
+            ProviderError::ContextLength { message } => write!(f, "Context length exceeded: {}", message),
+            ProviderError::Server { status, message } => write!(f, "Server error (HTTP {}): {}", status, message),
+            ProviderError::Network { message } => write!(f, "Network error: {}", message),
+            ProviderError::Http { status, message } => write!(f, "HTTP {}: {}", status, message),
+            ProviderError::InvalidResponse { message } => write!(f, "Invalid response: {}", message),
+            ProviderError::PromptBlocked { reason } => write!(f, "The prompt was blocked: {}", reason),
+            ProviderError::ResponseBlocked { reason, categories } if categories.is_empty() => {
+                write!(f, "The response was blocked: {}", reason)
+            }
+            ProviderError::ResponseBlocked { reason, categories } => {
+                write!(f, "The response was blocked: {} ({})", reason, categories.join(", "))
+            }
+            ProviderError::StructuredOutput { error } => write!(f, "{}", error),
+            ProviderError::Cancelled => write!(f, "Generation cancelled"),
+            ProviderError::Other { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            ProviderError::InvalidResponse { message: e.to_string() }
+        } else {
+            ProviderError::Network { message: e.to_string() }
+        }
+    }
+}
+
+impl From<StructuredOutputError> for ProviderError {
+    fn from(error: StructuredOutputError) -> Self {
+        ProviderError::StructuredOutput { error }
+    }
+}
+
+impl From<String> for ProviderError {
+    fn from(message: String) -> Self {
+        ProviderError::Other { message }
+    }
+}
+
+impl From<&str> for ProviderError {
+    fn from(message: &str) -> Self {
+        ProviderError::Other { message: message.to_string() }
+    }
+}
+
+impl ProviderError {
+    /// Whether the request may succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ProviderError::RateLimited { .. } | ProviderError::Server { .. })
+    }
+
+    /// Classifies a non-success HTTP response from its status, headers and body.
+    pub fn from_response(status: u16, retry_after: Option<Duration>, body: &str) -> Self {
+        let message = error_message(body);
+        let lower = body.to_lowercase();
+        if is_context_overflow(&lower) {
+            return ProviderError::ContextLength { message };
+        }
+        match status {
+            401 | 403 => ProviderError::Auth { status, message },
+            429 => ProviderError::RateLimited { message, retry_after: retry_after.map(|d| d.as_secs()) },
+            // 529 is Anthropic's "overloaded"
+            500..=599 => ProviderError::Server { status, message },
+            _ if lower.contains("overloaded") => ProviderError::Server { status, message },
+            _ => ProviderError::Http { status, message },
+        }
+    }
+
+    /// Classifies an error object sent inside a stream (OpenAI/llama-server `error`, Anthropic `error` event).
+    pub fn from_stream_error(v: &Value) -> Self {
+        let err = v.get("error").unwrap_or(v);
+        let message = err
+            .get("message")
+            .and_then(|m| m.as_str())
+            .or_else(|| err.as_str())
+            .unwrap_or("stream error")
+            .to_string();
+        let kind = err.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let code = err.get("code").and_then(|c| c.as_u64()).unwrap_or(0) as u16;
+        if is_context_overflow(&message.to_lowercase()) || kind.contains("context") {
+            return ProviderError::ContextLength { message };
+        }
+        match kind {
+            "rate_limit_error" => ProviderError::RateLimited { message, retry_after: None },
+            "overloaded_error" | "api_error" | "server_error" => ProviderError::Server { status: code.max(500), message },
+            "authentication_error" | "permission_error" => ProviderError::Auth { status: code.max(401), message },
+            _ => ProviderError::InvalidResponse { message },
+        }
+    }
+}
+
+/// Recognizes "prompt too long" errors across OpenAI, Anthropic, Gemini, Ollama and llama-server.
+fn is_context_overflow(lower: &str) -> bool {
+    [
+        "context_length_exceeded",
+        "maximum context length",
+        "context length",
+        "context window",
+        "exceeds the available context size",
+        "prompt is too long",
+        "input token count",
+        "too many tokens",
+    ]
+    .iter()
+    .any(|needle| lower.contains(needle))
+}
+
+/// Pulls the human-readable message out of the usual error body shapes:
+/// `{"error": {"message": ..}}`, `{"error": ".."}` or `{"message": ..}`; falls back to the raw text.
+fn error_message(body: &str) -> String {
+    let parsed = serde_json::from_str::<Value>(body).ok();
+    let from_json = parsed.as_ref().and_then(|v| {
+        v.pointer("/error/message")
+            .and_then(|m| m.as_str())
+            .or_else(|| v.get("error").and_then(|e| e.as_str()))
+            .or_else(|| v.get("message").and_then(|m| m.as_str()))
+            .map(|s| s.to_string())
+    });
+    let text = from_json.unwrap_or_else(|| body.trim().to_string());
+    if text.chars().count() > MAX_MESSAGE_LEN {
+        format!("{}...", text.chars().take(MAX_MESSAGE_LEN).collect::<String>())
+    } else {
+        text
+    }
+}
@@ -0,0 +1,240 @@
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use crate::db::SettingsPayload;
+use crate::streaming::{SseDecoder, StreamSink};
+use super::error::ProviderError;
+use super::http::{send_with_retry, with_headers};
+use super::{fill_if_empty, ChatRequest, ChatResponse, Provider, Usage};
+
+const DEFAULT_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
+const DEFAULT_MODEL: &str = "gemini-1.5-flash";
+
+/// Finish reasons that mean the answer was withheld by a filter.
+const BLOCK_REASONS: &[&str] = &["SAFETY", "RECITATION", "BLOCKLIST", "PROHIBITED_CONTENT", "SPII", "IMAGE_SAFETY"];
+
+/// Google Gemini `generateContent` API.
+pub struct GeminiProvider;
+
+impl Provider for GeminiProvider {
+    fn apply_defaults(&self, req: &mut ChatRequest, settings: &SettingsPayload) {
+        fill_if_empty(&mut req.base_url, settings.gemini_base.as_deref().unwrap_or(""));
+        fill_if_empty(&mut req.api_key, settings.gemini_key.as_deref().unwrap_or(""));
+        fill_if_empty(&mut req.model, settings.gemini_model.as_deref().unwrap_or(""));
+    }
+
+    fn chat<'a>(
+        &'a self,
+        req: &'a ChatRequest,
+        sink: Option<&'a StreamSink>,
+    ) -> BoxFuture<'a, Result<ChatResponse, ProviderError>> {
+        Box::pin(async move {
+            let base = req
+                .base_url
+                .as_deref()
+                .map(|s| s.trim().trim_end_matches('/'))
+                .filter(|s| !s.is_empty())
+                .unwrap_or(DEFAULT_BASE);
+            let model = req
+                .model
+                .as_deref()
+                .map(|m| m.trim().trim_start_matches("models/"))
+                .filter(|m| !m.is_empty())
+                .unwrap_or(DEFAULT_MODEL)
+                .to_string();
+            let url = if sink.is_some() {
+                format!("{}/models/{}:streamGenerateContent?alt=sse", base, model)
+            } else {
+                format!("{}/models/{}:generateContent", base, model)
+            };
+            let body = build_body(req);
+
+            let client = req.http_client();
+            let response = send_with_retry(|| {
+                let builder = client
+                    .post(&url)
+                    .header("x-goog-api-key", req.api_key.as_deref().unwrap_or(""))
+                    .json(&body);
+                with_headers(builder, req.extra_headers.as_ref())
+            })
+            .await?;
+
+            let resp = match sink {
+                Some(sink) => read_stream(response, sink, &model).await?,
+                None => {
+                    let v: Value = response.json().await?;
+                    let mut acc = Accumulator::new(&model);
+                    acc.feed(&v, None);
+                    acc
+                }
+            };
+            resp.finish()
+        })
+    }
+}
+
+fn build_body(req: &ChatRequest) -> Value {
+    let system: Vec<Value> = req
+        .messages
+        .iter()
+        .filter(|m| m.role == "system")
+        .map(|m| json!({"text": m.content}))
+        .collect();
+    // Gemini knows only "user" and "model" roles
+    let contents: Vec<Value> = req
+        .messages
+        .iter()
+        .filter(|m| m.role != "system")
+        .map(|m| {
+            let role = if m.role == "assistant" { "model" } else { "user" };
+            let mut parts = vec![json!({"text": m.content})];
+            for image in m.image_list() {
+                parts.push(json!({"inline_data": {"mime_type": image.mime(), "data": image.base64()}}));
+            }
+            json!({"role": role, "parts": parts})
+        })
+        .collect();
+
+    let mut config = serde_json::Map::new();
+    if let Some(t) = req.temperature { config.insert("temperature".into(), json!(t)); }
+    if let Some(p) = req.top_p { config.insert("topP".into(), json!(p)); }
+    if let Some(k) = req.top_k.filter(|k| *k > 0) { config.insert("topK".into(), json!(k)); }
+    if let Some(n) = req.max_tokens.filter(|n| *n > 0) { config.insert("maxOutputTokens".into(), json!(n)); }
+    if let Some(stop) = req.stop.as_ref().filter(|s| !s.is_empty()) { config.insert("stopSequences".into(), json!(stop)); }
+    if let Some(seed) = req.seed { config.insert("seed".into(), json!(seed)); }
+    if let Some(schema) = &req.response_schema {
+        config.insert("responseMimeType".into(), json!("application/json"));
+        config.insert("responseJsonSchema".into(), schema.clone());
+    }
+
+    let mut body = json!({ "contents": contents });
+    if !system.is_empty() { body["systemInstruction"] = json!({"parts": system}); }
+    if !config.is_empty() { body["generationConfig"] = Value::Object(config); }
+    if let (Some(Value::Object(extra)), Some(map)) = (req.extra.as_ref(), body.as_object_mut()) {
+        for (k, v) in extra {
+            map.insert(k.clone(), v.clone());
+        }
+    }
+    body
+}
+
+/// Collects text, finish reason and usage from one or more `GenerateContentResponse` objects.
+struct Accumulator {
+    content: String,
+    // Thought summaries (`thought: true` parts)
+    reasoning: String,
+    model: String,
+    finish_reason: Option<String>,
+    blocked_categories: Vec<String>,
+    prompt_block: Option<String>,
+    usage: Option<Usage>,
+}
+
+impl Accumulator {
+    fn new(model: &str) -> Self {
+        Self {
+            content: String::new(),
+            reasoning: String::new(),
+            model: model.to_string(),
+            finish_reason: None,
+            blocked_categories: Vec::new(),
+            prompt_block: None,
+            usage: None,
+        }
+    }
+
+    fn feed(&mut self, v: &Value, sink: Option<&StreamSink>) {
+        if let Some(reason) = v.pointer("/promptFeedback/blockReason").and_then(|r| r.as_str()) {
+            self.prompt_block = Some(reason.to_string());
+        }
+        if let Some(m) = v.get("modelVersion").and_then(|m| m.as_str()) {
+            self.model = m.to_string();
+        }
+        if let Some(u) = v.get("usageMetadata") {
+            self.usage = Some(Usage {
+                prompt_tokens: u.get("promptTokenCount").and_then(|x| x.as_u64()),
+                completion_tokens: u.get("candidatesTokenCount").and_then(|x| x.as_u64()),
+                total_tokens: u.get("totalTokenCount").and_then(|x| x.as_u64()),
+            });
+        }
+        let Some(candidate) = v.get("candidates").and_then(|c| c.get(0)) else { return; };
+        if let Some(parts) = candidate.pointer("/content/parts").and_then(|p| p.as_array()) {
+            for part in parts {
+                let Some(text) = part.get("text").and_then(|t| t.as_str()) else { continue; };
+                if part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false) {
+                    self.reasoning.push_str(text);
+                    if let Some(sink) = sink { sink.reasoning(text); }
+                } else {
+                    self.content.push_str(text);
+                    if let Some(sink) = sink { sink.token(text); }
+                }
+            }
+        }
+        if let Some(reason) = candidate.get("finishReason").and_then(|r| r.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+        }
+        if let Some(ratings) = candidate.get("safetyRatings").and_then(|r| r.as_array()) {
+            for r in ratings {
+                if r.get("blocked").and_then(|b| b.as_bool()).unwrap_or(false) {
+                    if let Some(cat) = r.get("category").and_then(|c| c.as_str()) {
+                        self.blocked_categories.push(cat.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Result<ChatResponse, ProviderError> {
+        if let Some(reason) = self.prompt_block {
+            return Err(ProviderError::PromptBlocked { reason });
+        }
+        let reason = self.finish_reason.unwrap_or_default();
+        let blocked = BLOCK_REASONS.contains(&reason.as_str());
+        if blocked && self.content.is_empty() {
+            return Err(ProviderError::ResponseBlocked { reason, categories: self.blocked_categories });
+        }
+        let finish_reason = match reason.as_str() {
+            "" => None,
+            "STOP" => Some("stop".to_string()),
+            "MAX_TOKENS" => Some("length".to_string()),
+            _ if blocked => Some("content_filter".to_string()),
+            other => Some(other.to_lowercase()),
+        };
+        Ok(ChatResponse {
+            content: self.content,
+            role: "assistant".to_string(),
+            finish_reason,
+            usage: self.usage,
+            model: self.model,
+            latency_ms: 0,
+            tool_calls: None,
+            reasoning: Some(self.reasoning).filter(|r| !r.is_empty()),
+            parsed: None,
+            cached: false,
+        })
+    }
+}
+
+async fn read_stream(response: reqwest::Response, sink: &StreamSink, model: &str) -> Result<Accumulator, ProviderError> {
+    let mut decoder = SseDecoder::default();
+    let mut acc = Accumulator::new(model);
+    let mut stream = response.bytes_stream();
+    let mut handle_data = |data: &str| -> Result<(), ProviderError> {
+        let Ok(v) = serde_json::from_str::<Value>(data) else { return Ok(()); };
+        if v.get("error").is_some() {
+            return Err(ProviderError::from_stream_error(&v));
+        }
+        acc.feed(&v, Some(sink));
+        Ok(())
+    };
+    while let Some(item) = stream.next().await {
+        let chunk = item?;
+        for data in decoder.push(&chunk) {
+            handle_data(&data)?;
+        }
+    }
+    if let Some(data) = decoder.finish() {
+        handle_data(&data)?;
+    }
+    Ok(acc)
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use reqwest::header::HeaderMap;
+use tauri::Manager;
+use tokio::time::sleep;
+use crate::db::SettingsPayload;
+use super::error::ProviderError;
+
+/// Extra attempts after the first request for 429 and 5xx replies.
+const MAX_RETRIES: u32 = 3;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// A server asking to wait longer than this gets its error returned instead.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+const DEFAULT_CONNECT_TIMEOUT_SECS: i64 = 20;
+const DEFAULT_READ_TIMEOUT_SECS: i64 = 300;
+
+/// The shared HTTP client, built from the network settings on first use and
+/// rebuilt after they change. `reqwest::Client` is a cheap handle to one connection pool.
+#[derive(Default)]
+pub struct HttpClientState(pub Mutex<Option<reqwest::Client>>);
+
+fn default_user_agent() -> String {
+    format!("TalkyAI/{}", env!("CARGO_PKG_VERSION"))
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// Builds a client with the proxy, no-proxy list, extra CA bundle, timeouts and User-Agent from settings.
+pub fn build_client(settings: &SettingsPayload) -> Result<reqwest::Client, String> {
+    let user_agent = non_empty(&settings.user_agent).map(str::to_string).unwrap_or_else(default_user_agent);
+    let mut builder = reqwest::Client::builder()
+        .user_agent(user_agent)
+        .tcp_keepalive(Duration::from_secs(30))
+        .pool_idle_timeout(Duration::from_secs(90));
+
+    let connect = settings.connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS);
+    if connect > 0 {
+        builder = builder.connect_timeout(Duration::from_secs(connect as u64));
+    }
+    // Applies between reads, so long streams and downloads are fine as long as data keeps coming
+    let read = settings.read_timeout_secs.unwrap_or(DEFAULT_READ_TIMEOUT_SECS);
+    if read > 0 {
+        builder = builder.read_timeout(Duration::from_secs(read as u64));
+    }
+
+    if let Some(url) = non_empty(&settings.http_proxy) {
+        let proxy = reqwest::Proxy::all(url).map_err(|e| format!("Invalid proxy URL '{}': {}", url, e))?;
+        builder = builder.proxy(proxy.no_proxy(non_empty(&settings.no_proxy).and_then(reqwest::NoProxy::from_string)));
+    }
+
+    if let Some(path) = non_empty(&settings.ca_bundle_path) {
+        let pem = std::fs::read(path).map_err(|e| format!("Cannot read CA bundle '{}': {}", path, e))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| format!("Invalid CA bundle '{}': {}", path, e))?;
+        if certs.is_empty() {
+            return Err(format!("CA bundle '{}' contains no certificates", path));
+        }
+        println!("[providers.http.build_client] Adding {} CA certificate(s) from {}", certs.len(), path);
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder.build().map_err(|e| format!("HTTP client creation error: {}", e))
+}
+
+/// Returns the shared client, building it from settings if needed.
+pub fn client(app: &tauri::AppHandle) -> Result<reqwest::Client, String> {
+    let state = app.state::<HttpClientState>();
+    let mut cached = state.0.lock().map_err(|e| e.to_string())?;
+    if let Some(client) = cached.as_ref() {
+        return Ok(client.clone());
+    }
+    let settings = crate::db::load_stored_settings(app)?;
+    let client = build_client(&settings)?;
+    *cached = Some(client.clone());
+    Ok(client)
+}
+
+/// Drops the cached client so the next request picks up new network settings.
+pub fn reset_client(app: &tauri::AppHandle) {
+    if let Some(state) = app.try_state::<HttpClientState>() {
+        if let Ok(mut cached) = state.0.lock() {
+            *cached = None;
+        }
+    }
+}
+
+/// Reads `retry-after-ms` (OpenAI) or `Retry-After` in seconds.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(|s| s.trim().to_string());
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        return Some(Duration::from_millis(ms.max(0.0) as u64));
+    }
+    header("retry-after")
+        .and_then(|v| v.parse::<f64>().ok())
+        .map(|secs| Duration::from_millis((secs.max(0.0) * 1000.0) as u64))
+}
+
+/// Adds a provider profile's extra headers (e.g. `OpenAI-Organization`, gateway auth) to a request.
+pub fn with_headers(mut builder: reqwest::RequestBuilder, headers: Option<&HashMap<String, String>>) -> reqwest::RequestBuilder {
+    for (name, value) in headers.into_iter().flatten() {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    builder
+}
+
+/// Sends the request built by `build`, turning non-success replies into a `ProviderError`.
+/// Rate limits and server errors are retried with exponential backoff, waiting for
+/// `Retry-After` when the server sends it. `build` is called again for every attempt.
+pub async fn send_with_retry<F>(build: F) -> Result<reqwest::Response, ProviderError>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let mut attempt: u32 = 0;
+    loop {
+        let response = build().send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let wait = retry_after(response.headers());
+        let text = response.text().await.unwrap_or_default();
+        let err = ProviderError::from_response(status.as_u16(), wait, &text);
+        if !err.is_retryable() || attempt >= MAX_RETRIES {
+            return Err(err);
+        }
+        let backoff = (BASE_DELAY * 2u32.pow(attempt)).min(MAX_DELAY);
+        let delay = match wait {
+            Some(w) if w > MAX_RETRY_AFTER => return Err(err),
+            Some(w) => w.max(backoff),
+            None => backoff,
+        };
+        attempt += 1;
+        println!("[providers.http] {}; retry {}/{} in {:?}", err, attempt, MAX_RETRIES, delay);
+        sleep(delay).await;
+    }
+}
@@ -0,0 +1,49 @@
+use futures::future::BoxFuture;
+use serde_json::json;
+use crate::db::SettingsPayload;
+use crate::streaming::StreamSink;
+use super::error::ProviderError;
+use super::{fill_if_empty, openai, ChatRequest, ChatResponse, Provider};
+
+/// The managed local llama-server, reached through its OpenAI-compatible endpoint.
+pub struct LlamaCppProvider;
+
+impl Provider for LlamaCppProvider {
+    fn apply_defaults(&self, req: &mut ChatRequest, settings: &SettingsPayload) {
+        fill_if_empty(&mut req.model, &settings.model_repo);
+        if req.port.is_none() {
+            req.port = settings.server_port.and_then(|p| u16::try_from(p).ok());
+        }
+    }
+
+    fn chat<'a>(
+        &'a self,
+        req: &'a ChatRequest,
+        sink: Option<&'a StreamSink>,
+    ) -> BoxFuture<'a, Result<ChatResponse, ProviderError>> {
+        Box::pin(async move {
+            let port = req.port.unwrap_or(8080);
+            let url = format!("http://127.0.0.1:{}/v1/chat/completions", port);
+            let model = req
+                .model
+                .clone()
+                .filter(|m| !m.trim().is_empty())
+                .unwrap_or_else(|| "local-model".to_string());
+            // Always streamed: llama-server stops decoding only when a streaming client disconnects,
+            // so an aborted request would otherwise keep the server busy
+            let discard = StreamSink::discard();
+            let sink = sink.unwrap_or(&discard);
+            let mut body = openai::build_body(req, &model, true);
+            // llama-server specific samplers
+            if let Some(k) = req.top_k { body["top_k"] = json!(k); }
+            if let Some(p) = req.min_p { body["min_p"] = json!(p); }
+            if let Some(n) = req.repeat_last_n { body["repeat_last_n"] = json!(n); }
+            // llama-server turns `json_schema` into a sampling grammar
+            if let Some(schema) = &req.response_schema {
+                if let Some(map) = body.as_object_mut() { map.remove("response_format"); }
+                body["json_schema"] = schema.clone();
+            }
+            openai::send(&req.http_client(), &url, req.api_key.as_deref(), req.extra_headers.as_ref(), body, Some(sink), &model).await
+        })
+    }
+}
@@ -0,0 +1,588 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::attachments::ImageAttachment;
+use crate::cache::CacheSlot;
+use crate::db::{ProviderProfile, SettingsPayload};
+use crate::secrets;
+use crate::generation::run_cancellable;
+use crate::streaming::{ensure_request_id, StreamSink, StreamTarget};
+use crate::tools::{ToolCall, ToolDefinition, ToolRegistry};
+use error::ProviderError;
+
+pub mod error;
+pub mod http;
+pub mod openai;
+pub mod deepseek;
+pub mod ollama;
+pub mod llama_cpp;
+pub mod anthropic;
+pub mod gemini;
+pub mod embeddings;
+pub mod models;
+pub mod compare;
+
+const DEFAULT_MAX_TOOL_ITERATIONS: u32 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    // Set on assistant messages that requested tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    // Set on "tool" messages carrying a tool result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Images for vision models; encoded by `prepare_images` before sending
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<ImageAttachment>>,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self { role: role.to_string(), content: content.into(), tool_calls: None, tool_call_id: None, name: None, images: None }
+    }
+
+    pub fn tool_result(call: &ToolCall, content: String) -> Self {
+        Self {
+            role: "tool".to_string(),
+            content,
+            tool_calls: None,
+            tool_call_id: Some(call.id.clone()),
+            name: Some(call.name.clone()),
+            images: None,
+        }
+    }
+
+    /// Attached images, if any.
+    pub fn image_list(&self) -> &[ImageAttachment] {
+        self.images.as_deref().unwrap_or(&[])
+    }
+}
+
+/// Loads and downscales all image attachments so providers can send them as base64.
+pub(crate) async fn prepare_images(messages: &mut [ChatMessage]) -> Result<(), String> {
+    let total: usize = messages.iter().map(|m| m.image_list().len()).sum();
+    if total > crate::attachments::MAX_IMAGES {
+        return Err(format!("Too many images: {} (at most {} per request)", total, crate::attachments::MAX_IMAGES));
+    }
+    for m in messages.iter_mut() {
+        if let Some(images) = m.images.take() {
+            m.images = Some(crate::attachments::prepare(images).await?);
+        }
+    }
+    Ok(())
+}
+
+/// Provider-agnostic chat request accepted by the `chat` command.
+/// Empty endpoint/model fields are filled from saved settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatRequest {
+    // Backend id: "openai", "deepseek", "anthropic", "gemini", "ollama" or "local" (llama.cpp).
+    // May be omitted when `profile_id` (or the project's profile) supplies it.
+    #[serde(default)]
+    pub provider: String,
+    // Saved endpoint profile; its kind, base URL, key, model and headers fill unset fields
+    pub profile_id: Option<i64>,
+    // Extra HTTP headers sent with every provider request
+    pub extra_headers: Option<HashMap<String, String>>,
+    pub model: Option<String>,
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    // llama.cpp server port
+    pub port: Option<u16>,
+    // May be empty in `compare_models` targets, which share the comparison's messages
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
+    // Sampling
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<i64>,
+    pub min_p: Option<f32>,
+    pub max_tokens: Option<i64>,
+    pub repeat_last_n: Option<i64>,
+    pub stop: Option<Vec<String>>,
+    pub seed: Option<i64>,
+    // Provider-specific fields merged into the request body as-is
+    pub extra: Option<Value>,
+    // Streaming
+    pub stream: Option<bool>,
+    pub request_id: Option<String>,
+    // Tool calling: names of registered tools the model may call
+    pub tools: Option<Vec<String>>,
+    pub max_tool_iterations: Option<u32>,
+    // Structured output: JSON schema the answer must match, and how many times to ask the model to fix a mismatch
+    pub response_schema: Option<Value>,
+    pub repair_attempts: Option<u32>,
+    // Project the request is billed to in the usage ledger
+    pub project_id: Option<i64>,
+    // Skip the response cache for this request
+    pub bypass_cache: Option<bool>,
+    // Resolved from `tools` by the chat command
+    #[serde(skip)]
+    pub tool_definitions: Vec<ToolDefinition>,
+    // Shared HTTP client, set by the chat command
+    #[serde(skip)]
+    pub client: Option<reqwest::Client>,
+}
+
+impl ChatRequest {
+    pub fn http_client(&self) -> reqwest::Client {
+        self.client.clone().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub total_tokens: Option<u64>,
+}
+
+impl Usage {
+    /// Reads an OpenAI-style `usage` object.
+    pub fn from_openai(v: &Value) -> Option<Usage> {
+        if !v.is_object() { return None; }
+        let prompt_tokens = v.get("prompt_tokens").and_then(|x| x.as_u64());
+        let completion_tokens = v.get("completion_tokens").and_then(|x| x.as_u64());
+        let total_tokens = v
+            .get("total_tokens")
+            .and_then(|x| x.as_u64())
+            .or_else(|| Some(prompt_tokens? + completion_tokens?));
+        Some(Usage { prompt_tokens, completion_tokens, total_tokens })
+    }
+
+    /// Reads Ollama's `prompt_eval_count`/`eval_count` from a final response object.
+    pub fn from_ollama(v: &Value) -> Option<Usage> {
+        Self::from_counts(
+            v.get("prompt_eval_count").and_then(|x| x.as_u64()),
+            v.get("eval_count").and_then(|x| x.as_u64()),
+        )
+    }
+
+    /// Reads llama-server `timings` (`prompt_n`/`predicted_n`).
+    pub fn from_llama_timings(v: &Value) -> Option<Usage> {
+        Self::from_counts(
+            v.get("prompt_n").and_then(|x| x.as_u64()),
+            v.get("predicted_n").and_then(|x| x.as_u64()),
+        )
+    }
+
+    /// Finds usage in a raw response body of any supported backend.
+    pub fn from_body(v: &Value) -> Option<Usage> {
+        v.get("usage")
+            .and_then(Usage::from_openai)
+            .or_else(|| v.get("timings").and_then(Usage::from_llama_timings))
+            .or_else(|| Usage::from_ollama(v))
+    }
+
+    fn from_counts(prompt_tokens: Option<u64>, completion_tokens: Option<u64>) -> Option<Usage> {
+        if prompt_tokens.is_none() && completion_tokens.is_none() { return None; }
+        Some(Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: Some(prompt_tokens.unwrap_or(0) + completion_tokens.unwrap_or(0)),
+        })
+    }
+
+    /// Adds up usage of several round-trips (tool calling loop).
+    fn add(total: Option<Usage>, next: Option<Usage>) -> Option<Usage> {
+        let sum = |a: Option<u64>, b: Option<u64>| match (a, b) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+        };
+        match (total, next) {
+            (Some(a), Some(b)) => Some(Usage {
+                prompt_tokens: sum(a.prompt_tokens, b.prompt_tokens),
+                completion_tokens: sum(a.completion_tokens, b.completion_tokens),
+                total_tokens: sum(a.total_tokens, b.total_tokens),
+            }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub content: String,
+    pub role: String,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Usage>,
+    pub model: String,
+    pub latency_ms: u64,
+    // Tool calls the model requested and the chat loop did not handle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    // Reasoning ("thinking") text, kept out of `content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    // Validated JSON when `response_schema` was set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<Value>,
+    // Served from the response cache without calling the provider
+    #[serde(default)]
+    pub cached: bool,
+}
+
+impl ChatResponse {
+    /// Moves inline `<think>` blocks from `content` into `reasoning`.
+    fn split_reasoning(&mut self) {
+        if let (content, Some(inline)) = crate::reasoning::split(&self.content) {
+            self.content = content;
+            self.reasoning = crate::reasoning::join(self.reasoning.take(), Some(inline));
+        }
+    }
+}
+
+/// A chat backend. Adding a backend means implementing this trait and
+/// registering it in `provider_for`.
+pub trait Provider: Send + Sync {
+    /// Fills endpoint, credentials and model from saved settings where the request left them empty.
+    fn apply_defaults(&self, req: &mut ChatRequest, settings: &SettingsPayload);
+
+    /// Sends the request. When `sink` is set the provider streams and emits tokens through it.
+    fn chat<'a>(
+        &'a self,
+        req: &'a ChatRequest,
+        sink: Option<&'a StreamSink>,
+    ) -> BoxFuture<'a, Result<ChatResponse, ProviderError>>;
+}
+
+pub fn provider_for(id: &str) -> Result<Box<dyn Provider>, String> {
+    match id.trim().to_lowercase().as_str() {
+        "openai" => Ok(Box::new(openai::OpenAiProvider)),
+        "deepseek" => Ok(Box::new(deepseek::DeepSeekProvider)),
+        "anthropic" => Ok(Box::new(anthropic::AnthropicProvider)),
+        "gemini" => Ok(Box::new(gemini::GeminiProvider)),
+        "ollama" => Ok(Box::new(ollama::OllamaProvider)),
+        "local" | "llamacpp" | "llama-cpp" => Ok(Box::new(llama_cpp::LlamaCppProvider)),
+        other => Err(format!("Unknown provider '{}'", other)),
+    }
+}
+
+/// Whether `id` names the managed llama-server, spelled any way `provider_for` accepts.
+fn is_local(id: &str) -> bool {
+    matches!(id.trim().to_lowercase().as_str(), "local" | "llamacpp" | "llama-cpp")
+}
+
+/// Provider id stored in the usage ledger ("llamacpp"/"llama-cpp" are recorded as "local").
+fn ledger_provider(id: &str) -> String {
+    if is_local(id) { "local".to_string() } else { id.trim().to_lowercase() }
+}
+
+/// Adds a finished request to the usage ledger; a failed write is only logged.
+pub(crate) fn record_usage(
+    app: &tauri::AppHandle,
+    project_id: Option<i64>,
+    provider: &str,
+    model: &str,
+    usage: Option<&Usage>,
+    latency_ms: u64,
+) {
+    let tokens = |n: Option<u64>| n.map(|n| n as i64);
+    let record = crate::db::UsageRecord {
+        project_id,
+        provider: ledger_provider(provider),
+        model: model.to_string(),
+        prompt_tokens: tokens(usage.and_then(|u| u.prompt_tokens)),
+        completion_tokens: tokens(usage.and_then(|u| u.completion_tokens)),
+        total_tokens: tokens(usage.and_then(|u| u.total_tokens)),
+        latency_ms: latency_ms as i64,
+    };
+    if let Err(e) = crate::db::record_usage(app, &record) {
+        println!("[providers.record_usage] failed to record usage: {}", e);
+    }
+}
+
+/// Replaces a missing or blank value with `default`.
+pub(crate) fn fill_if_empty(value: &mut Option<String>, default: &str) {
+    if value.as_deref().map(|s| s.trim().is_empty()).unwrap_or(true) && !default.trim().is_empty() {
+        *value = Some(default.to_string());
+    }
+}
+
+/// Settings with API keys decrypted; only requests built here see plain keys.
+pub(crate) fn request_settings(app: &tauri::AppHandle) -> Result<SettingsPayload, String> {
+    let mut settings = crate::db::load_stored_settings(app)?;
+    settings.api_key = secrets::decrypt(app, &settings.api_key)?;
+    if let Some(key) = settings.anthropic_key.as_mut() { *key = secrets::decrypt(app, key)?; }
+    if let Some(key) = settings.gemini_key.as_mut() { *key = secrets::decrypt(app, key)?; }
+    Ok(settings)
+}
+
+/// Provider profile with its API key decrypted.
+pub(crate) fn load_profile(app: &tauri::AppHandle, id: i64) -> Result<ProviderProfile, String> {
+    let mut profile = crate::db::load_provider_profile(app, id)?;
+    profile.api_key = secrets::decrypt(app, &profile.api_key)?;
+    Ok(profile)
+}
+
+/// The frontend only ever sees masked keys; a mask sent back means "use the stored key".
+pub(crate) fn unmask(key: &mut Option<String>) {
+    if key.as_deref().map(secrets::is_mask).unwrap_or(false) {
+        *key = None;
+    }
+}
+
+/// Key for the legacy OpenAI-compatible commands: the caller's key, or the stored one
+/// when it is empty or masked.
+pub(crate) fn resolve_api_key(app: &tauri::AppHandle, api_key: String) -> Result<String, String> {
+    if !api_key.trim().is_empty() && !secrets::is_mask(&api_key) {
+        return Ok(api_key);
+    }
+    Ok(request_settings(app)?.api_key)
+}
+
+/// Fills unset endpoint fields from the request's provider profile, falling back to the
+/// profile linked to `project_id`. Explicit request values always win.
+pub(crate) fn apply_profile(app: &tauri::AppHandle, req: &mut ChatRequest) -> Result<(), String> {
+    let profile_id = match (req.profile_id, req.project_id) {
+        (Some(id), _) => Some(id),
+        (None, Some(project)) if req.provider.trim().is_empty() => crate::db::project_provider_id(app, project)?,
+        _ => None,
+    };
+    let Some(id) = profile_id else { return Ok(()); };
+    let profile = load_profile(app, id)?;
+    if req.provider.trim().is_empty() {
+        req.provider = profile.kind.clone();
+    }
+    if profile.kind == "local" {
+        // Local profiles store the llama-server address; the provider only needs the port
+        if req.port.is_none() {
+            req.port = local_port(&profile.base_url);
+        }
+    } else {
+        fill_if_empty(&mut req.base_url, &profile.base_url);
+    }
+    fill_if_empty(&mut req.api_key, &profile.api_key);
+    fill_if_empty(&mut req.model, &profile.default_model);
+    if !profile.extra_headers.is_empty() {
+        let mut headers = profile.extra_headers;
+        headers.extend(req.extra_headers.take().unwrap_or_default());
+        req.extra_headers = Some(headers);
+    }
+    Ok(())
+}
+
+/// Port of the llama-server address stored in a local profile, e.g. `http://127.0.0.1:8080/v1`
+/// or `127.0.0.1:8080`. `None` when the address has no explicit port.
+pub(crate) fn local_port(base_url: &str) -> Option<u16> {
+    let base = base_url.trim();
+    let url = if base.contains("://") { base.to_string() } else { format!("http://{}", base) };
+    reqwest::Url::parse(&url).ok()?.port()
+}
+
+/// Applies generation controls stored in settings to fields the caller did not set.
+fn apply_generation_defaults(req: &mut ChatRequest, settings: &SettingsPayload) {
+    req.temperature = req.temperature.or(settings.temperature);
+    req.top_p = req.top_p.or(settings.top_p);
+    req.top_k = req.top_k.or(settings.top_k);
+    req.min_p = req.min_p.or(settings.min_p);
+    req.max_tokens = req.max_tokens.or(settings.max_tokens);
+    req.repeat_last_n = req.repeat_last_n.or(settings.repeat_last_n);
+}
+
+/// Sends the request and, while the model asks for tools, runs them and sends the results back.
+async fn run_with_tools(
+    app: &tauri::AppHandle,
+    provider: &dyn Provider,
+    req: &mut ChatRequest,
+    sink: Option<&StreamSink>,
+    registry: &ToolRegistry,
+) -> Result<ChatResponse, ProviderError> {
+    let max_iterations = req.max_tool_iterations.unwrap_or(DEFAULT_MAX_TOOL_ITERATIONS).max(1);
+    let mut usage: Option<Usage> = None;
+    // One call per tool round plus the final answer
+    for round in 0..=max_iterations {
+        let mut resp = provider.chat(req, sink).await?;
+        resp.split_reasoning();
+        usage = Usage::add(usage, resp.usage.take());
+        let calls = match resp.tool_calls.take() {
+            Some(calls) if !calls.is_empty() && !req.tool_definitions.is_empty() => calls,
+            other => {
+                resp.tool_calls = other;
+                resp.usage = usage;
+                return Ok(resp);
+            }
+        };
+        if round == max_iterations {
+            break;
+        }
+
+        let mut assistant = ChatMessage::new("assistant", resp.content);
+        assistant.tool_calls = Some(calls.clone());
+        req.messages.push(assistant);
+        for call in &calls {
+            let output = registry.execute(app, call).await;
+            req.messages.push(ChatMessage::tool_result(call, output));
+        }
+    }
+    Err(format!("Model kept calling tools after {} rounds", max_iterations).into())
+}
+
+/// Runs the request and validates the answer against `response_schema`, asking the model
+/// to repair a mismatching answer up to `repair_attempts` times. Only the validated answer is streamed.
+async fn run_structured(
+    app: &tauri::AppHandle,
+    provider: &dyn Provider,
+    req: &mut ChatRequest,
+    sink: Option<&StreamSink>,
+    registry: &ToolRegistry,
+) -> Result<ChatResponse, ProviderError> {
+    let Some(schema) = req.response_schema.clone() else {
+        return run_with_tools(app, provider, req, sink, registry).await;
+    };
+    let repair_attempts = req.repair_attempts.unwrap_or(0);
+    let mut usage: Option<Usage> = None;
+    let mut attempt = 0;
+    loop {
+        // Attempts are not streamed: only the validated answer reaches the sink
+        let mut resp = run_with_tools(app, provider, req, None, registry).await?;
+        usage = Usage::add(usage, resp.usage.take());
+        resp.usage = usage.clone();
+        match crate::schema::parse_and_validate(&schema, &resp.content) {
+            Ok(value) => {
+                resp.parsed = Some(value);
+                if let Some(sink) = sink {
+                    sink.reasoning(resp.reasoning.as_deref().unwrap_or(""));
+                    sink.token(&resp.content);
+                }
+                return Ok(resp);
+            }
+            Err(e) if attempt < repair_attempts => {
+                println!("[providers.run_structured] attempt {} rejected: {}", attempt, e);
+                req.messages.push(ChatMessage::new("assistant", resp.content));
+                req.messages.push(ChatMessage::new("user", e.repair_prompt()));
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn chat(app: tauri::AppHandle, window: tauri::Window, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+    run_chat(app, StreamTarget::Window(window), request).await
+}
+
+/// Cache slot for a resolved chat request; everything that shapes the answer is part of the key.
+fn cache_slot(app: &tauri::AppHandle, req: &ChatRequest) -> Option<CacheSlot> {
+    let deterministic = req.temperature.map(|t| t <= 0.0).unwrap_or(false) || req.seed.is_some();
+    let parts = json!({
+        "provider": req.provider,
+        "base_url": req.base_url,
+        "port": req.port,
+        "model": req.model,
+        "messages": req.messages,
+        "sampling": [req.temperature, req.top_p, req.min_p],
+        "top_k": req.top_k,
+        "max_tokens": req.max_tokens,
+        "repeat_last_n": req.repeat_last_n,
+        "stop": req.stop,
+        "seed": req.seed,
+        "extra": req.extra,
+        "response_schema": req.response_schema,
+    });
+    CacheSlot::open(app, req.bypass_cache, deterministic, "chat", &parts)
+}
+
+/// The body of the `chat` command, shared with `compare_models` and the HTTP gateway.
+/// Streamed tokens go to `target`.
+pub(crate) async fn run_chat(app: tauri::AppHandle, target: StreamTarget, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+    println!("[providers.chat] provider='{}', profile_id={:?}, model={:?}, messages_len={}, stream={:?}", request.provider, request.profile_id, request.model, request.messages.len(), request.stream);
+    let mut req = request;
+    let request_id = ensure_request_id(req.request_id.take());
+    req.request_id = Some(request_id.clone());
+    let sink = if req.stream.unwrap_or(false) {
+        Some(StreamSink::with_target(target, Some(request_id.clone())))
+    } else {
+        None
+    };
+    let result = generate(app, req, request_id, sink.as_ref()).await;
+    if let (Some(sink), Err(e)) = (&sink, &result) {
+        sink.fail(e);
+    }
+    result
+}
+
+async fn generate(app: tauri::AppHandle, mut req: ChatRequest, request_id: String, sink: Option<&StreamSink>) -> Result<ChatResponse, ProviderError> {
+    unmask(&mut req.api_key);
+    apply_profile(&app, &mut req)?;
+    let provider = provider_for(&req.provider)?;
+    if req.messages.is_empty() {
+        return Err("messages cannot be empty".into());
+    }
+    for m in req.messages.iter_mut() {
+        crate::reasoning::strip_history(&m.role, &mut m.content);
+    }
+    match request_settings(&app) {
+        Ok(settings) => {
+            apply_generation_defaults(&mut req, &settings);
+            provider.apply_defaults(&mut req, &settings);
+        }
+        Err(e) => println!("[providers.chat] settings unavailable, using request as-is: {}", e),
+    }
+
+    prepare_images(&mut req.messages).await?;
+    req.client = Some(http::client(&app)?);
+    if is_local(&req.provider) {
+        // A freshly started llama-server may still be loading the model
+        crate::backends::llama_cpp::status::wait_ready(&app, req.port.unwrap_or(8080)).await?;
+        if req.api_key.as_deref().unwrap_or("").trim().is_empty() {
+            req.api_key = crate::backends::llama_cpp::router::auth_token(&app, req.port.unwrap_or(8080));
+        }
+    }
+
+    let registry = match req.tools.as_ref().filter(|t| !t.is_empty()) {
+        Some(names) => {
+            let registry = ToolRegistry::load(&app)?;
+            req.tool_definitions = registry.definitions(names)?;
+            registry
+        }
+        None => ToolRegistry::default(),
+    };
+
+    // Tool calls have side effects, so only plain chats are cached
+    let cache = if req.tool_definitions.is_empty() { cache_slot(&app, &req) } else { None };
+    if let Some(hit) = cache.as_ref().and_then(|c| c.get(&app)) {
+        if let Ok(mut resp) = serde_json::from_str::<ChatResponse>(&hit) {
+            resp.cached = true;
+            resp.latency_ms = 0;
+            if let Some(sink) = sink {
+                sink.reasoning(resp.reasoning.as_deref().unwrap_or(""));
+                sink.token(&resp.content);
+                let usage = resp.usage.as_ref().and_then(|u| serde_json::to_value(u).ok());
+                sink.done(resp.finish_reason.clone(), usage);
+            }
+            return Ok(resp);
+        }
+    }
+
+    let started = Instant::now();
+    let result = run_cancellable(
+        &app,
+        &request_id,
+        run_structured(&app, provider.as_ref(), &mut req, sink, &registry),
+    )
+    .await;
+    let mut resp = result?;
+    resp.latency_ms = started.elapsed().as_millis() as u64;
+
+    // Prices are keyed by the requested model name, not the dated variant some APIs report
+    let model = req.model.clone().filter(|m| !m.trim().is_empty()).unwrap_or_else(|| resp.model.clone());
+    record_usage(&app, req.project_id, &req.provider, &model, resp.usage.as_ref(), resp.latency_ms);
+    if let (Some(cache), Ok(value)) = (&cache, serde_json::to_string(&resp)) {
+        cache.put(&app, &req.provider, &model, &value);
+    }
+
+    if let Some(sink) = sink {
+        let usage = resp.usage.as_ref().and_then(|u| serde_json::to_value(u).ok());
+        sink.done(resp.finish_reason.clone(), usage);
+    }
+    Ok(resp)
+}
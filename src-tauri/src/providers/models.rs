@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::Manager;
+use super::error::ProviderError;
+use super::http::{send_with_retry, with_headers};
+use super::{fill_if_empty, load_profile, local_port, request_settings, unmask};
+
+/// How long a model list from a remote endpoint is reused.
+const REMOTE_TTL: Duration = Duration::from_secs(300);
+/// llama-server can be restarted with another model at any time, so its list goes stale fast.
+const LOCAL_TTL: Duration = Duration::from_secs(5);
+
+/// Model lists per endpoint URL, with the time they were fetched.
+#[derive(Default)]
+pub struct ModelCacheState(pub Mutex<HashMap<String, (Instant, Vec<String>)>>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelList {
+    pub models: Vec<String>,
+    // "endpoint" (fresh), "cache" or "fallback" (the endpoint cannot list models)
+    pub source: String,
+    // Why the fallback was used
+    pub message: Option<String>,
+}
+
+fn is_loopback(url: &str) -> bool {
+    let host = url.split("://").nth(1).unwrap_or(url);
+    host.starts_with("127.") || host.starts_with("localhost") || host.starts_with("[::1]")
+}
+
+/// llama-server reports the model file path as the id; the file name is what users recognize.
+fn display_id(id: &str) -> String {
+    if id.to_lowercase().ends_with(".gguf") {
+        id.rsplit(['/', '\\']).next().unwrap_or(id).to_string()
+    } else {
+        id.to_string()
+    }
+}
+
+/// Reads `data[].id` (OpenAI, llama-server) or llama-server's `models[].model|name`.
+fn parse_models(v: &Value) -> Option<Vec<String>> {
+    let mut ids: Vec<String> = match v.get("data").and_then(|d| d.as_array()) {
+        Some(data) => data.iter().filter_map(|m| m.get("id")?.as_str().map(display_id)).collect(),
+        None => v
+            .get("models")?
+            .as_array()?
+            .iter()
+            .filter_map(|m| m.get("model").or_else(|| m.get("name"))?.as_str().map(display_id))
+            .collect(),
+    };
+    ids.sort();
+    ids.dedup();
+    Some(ids)
+}
+
+/// Base URL of the OpenAI-compatible API (the part before `/models` or `/chat/completions`).
+fn api_base(base: &str) -> String {
+    let base = base.trim().trim_end_matches('/');
+    base.strip_suffix("/chat/completions").unwrap_or(base).to_string()
+}
+
+/// Lists models of an OpenAI-compatible endpoint via `GET {base}/models`.
+/// The endpoint comes from `profile_id`, else `base_url`/`api_key`, else the OpenAI settings;
+/// `port` targets a local llama-server. Results are cached per endpoint (`refresh` skips the cache).
+/// Endpoints without the route yield the configured model with `source: "fallback"`.
+#[tauri::command]
+pub async fn list_openai_models(
+    app: tauri::AppHandle,
+    profile_id: Option<i64>,
+    base_url: Option<String>,
+    api_key: Option<String>,
+    port: Option<u16>,
+    refresh: Option<bool>,
+) -> Result<ModelList, ProviderError> {
+    println!("[providers.list_openai_models] profile_id={:?}, base_url={:?}, port={:?}, refresh={:?}", profile_id, base_url, port, refresh);
+    let mut base_url = base_url;
+    let mut api_key = api_key;
+    unmask(&mut api_key);
+    let mut fallback: Option<String> = None;
+    let mut headers = None;
+    let mut local = port.is_some();
+
+    if let Some(id) = profile_id {
+        let profile = load_profile(&app, id)?;
+        match profile.kind.as_str() {
+            "openai" | "deepseek" => fill_if_empty(&mut base_url, &profile.base_url),
+            "local" => {
+                local = true;
+                fill_if_empty(&mut base_url, &format!("{}/v1", profile.base_url.trim_end_matches('/')));
+            }
+            other => return Err(format!("Profile '{}' is a {} endpoint, not OpenAI-compatible", profile.name, other).into()),
+        }
+        fill_if_empty(&mut api_key, &profile.api_key);
+        fill_if_empty(&mut fallback, &profile.default_model);
+        headers = Some(profile.extra_headers).filter(|h| !h.is_empty());
+    }
+    if let Some(port) = port {
+        fill_if_empty(&mut base_url, &format!("http://127.0.0.1:{}/v1", port));
+    }
+    if let Ok(settings) = request_settings(&app) {
+        fill_if_empty(&mut base_url, &settings.api_base);
+        if local {
+            fill_if_empty(&mut fallback, &settings.model_repo);
+        } else {
+            fill_if_empty(&mut api_key, &settings.api_key);
+            fill_if_empty(&mut fallback, &settings.api_model);
+        }
+    }
+    if local {
+        // The llama-server router only answers requests carrying the gateway token
+        if let Some(port) = port.or_else(|| base_url.as_deref().and_then(local_port)) {
+            if let Some(token) = crate::backends::llama_cpp::router::auth_token(&app, port) {
+                fill_if_empty(&mut api_key, &token);
+            }
+        }
+    }
+    let base = api_base(base_url.as_deref().unwrap_or("https://api.openai.com/v1"));
+    let url = format!("{}/models", base);
+
+    let ttl = if is_loopback(&url) { LOCAL_TTL } else { REMOTE_TTL };
+    let cache = app.state::<ModelCacheState>();
+    let cached = cache.0.lock().map_err(|e| e.to_string())?.get(&url).cloned();
+    if let Some((fetched, models)) = &cached {
+        if !refresh.unwrap_or(false) && fetched.elapsed() < ttl {
+            return Ok(ModelList { models: models.clone(), source: "cache".to_string(), message: None });
+        }
+    }
+
+    let client = super::http::client(&app)?;
+    let fetched = async {
+        let response = send_with_retry(|| {
+            let mut req = with_headers(client.get(&url), headers.as_ref());
+            if let Some(key) = api_key.as_deref().filter(|k| !k.trim().is_empty()) {
+                req = req.header("Authorization", format!("Bearer {}", key));
+            }
+            req
+        })
+        .await?;
+        let v: Value = response.json().await?;
+        parse_models(&v).ok_or_else(|| ProviderError::InvalidResponse { message: "no `data` list in /models response".to_string() })
+    }
+    .await;
+
+    match fetched {
+        Ok(models) => {
+            println!("[providers.list_openai_models] {} model(s) from {}", models.len(), url);
+            cache.0.lock().map_err(|e| e.to_string())?.insert(url, (Instant::now(), models.clone()));
+            Ok(ModelList { models, source: "endpoint".to_string(), message: None })
+        }
+        // A wrong key should be fixed, not hidden behind a fallback
+        Err(e @ ProviderError::Auth { .. }) => Err(e),
+        Err(e) => {
+            println!("[providers.list_openai_models] {} failed: {}", url, e);
+            if let Some((_, models)) = cached {
+                return Ok(ModelList { models, source: "cache".to_string(), message: Some(e.to_string()) });
+            }
+            Ok(ModelList {
+                models: fallback.into_iter().collect(),
+                source: "fallback".to_string(),
+                message: Some(format!("This endpoint does not list models ({})", e)),
+            })
+        }
+    }
+}
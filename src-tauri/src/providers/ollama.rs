@@ -0,0 +1,194 @@
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use crate::backends::ollama::query::merge_json;
+use crate::db::SettingsPayload;
+use crate::streaming::StreamSink;
+use crate::tools::ToolCall;
+use super::error::ProviderError;
+use super::http::{send_with_retry, with_headers};
+use super::{fill_if_empty, openai, ChatMessage, ChatRequest, ChatResponse, Provider, Usage};
+
+const DEFAULT_BASE: &str = "http://127.0.0.1:11434";
+
+/// Ollama native `/api/chat` endpoint.
+pub struct OllamaProvider;
+
+impl Provider for OllamaProvider {
+    fn apply_defaults(&self, req: &mut ChatRequest, settings: &SettingsPayload) {
+        fill_if_empty(&mut req.base_url, settings.ollama_base.as_deref().unwrap_or(""));
+        fill_if_empty(&mut req.model, settings.ollama_model.as_deref().unwrap_or(""));
+        // Advanced params saved in settings apply only when the caller sent none
+        if req.extra.is_none() {
+            req.extra = settings
+                .ollama_params_json
+                .as_deref()
+                .filter(|s| !s.trim().is_empty())
+                .and_then(|s| serde_json::from_str::<Value>(s).ok());
+        }
+    }
+
+    fn chat<'a>(
+        &'a self,
+        req: &'a ChatRequest,
+        sink: Option<&'a StreamSink>,
+    ) -> BoxFuture<'a, Result<ChatResponse, ProviderError>> {
+        Box::pin(async move {
+            let base = req
+                .base_url
+                .as_deref()
+                .map(|s| s.trim().trim_end_matches('/'))
+                .filter(|s| !s.is_empty())
+                .unwrap_or(DEFAULT_BASE);
+            let url = format!("{}/api/chat", base);
+            let model = req.model.clone().unwrap_or_default();
+            // Always streamed: Ollama stops generating once a streaming client disconnects,
+            // which is how an aborted request is stopped
+            let body = build_body(req, &model, true);
+            let discard = StreamSink::discard();
+
+            let client = req.http_client();
+            let response = send_with_retry(|| with_headers(client.post(&url).json(&body), req.extra_headers.as_ref())).await?;
+            read_ndjson_stream(response, sink.unwrap_or(&discard), &model).await
+        })
+    }
+}
+
+/// Ollama takes tool arguments as objects and names tool results with `tool_name`.
+fn message_json(m: &ChatMessage) -> Value {
+    let mut v = json!({"role": m.role, "content": m.content});
+    if !m.image_list().is_empty() {
+        v["images"] = m.image_list().iter().map(|i| json!(i.base64())).collect();
+    }
+    if let Some(calls) = &m.tool_calls {
+        v["tool_calls"] = calls
+            .iter()
+            .map(|c| json!({"function": {"name": c.name, "arguments": c.arguments}}))
+            .collect();
+    }
+    if m.role == "tool" {
+        if let Some(name) = &m.name { v["tool_name"] = json!(name); }
+    }
+    v
+}
+
+fn parse_tool_calls(v: &Value) -> Vec<ToolCall> {
+    v.pointer("/message/tool_calls")
+        .and_then(|c| c.as_array())
+        .map(|calls| {
+            calls
+                .iter()
+                .enumerate()
+                .map(|(i, c)| ToolCall {
+                    id: c
+                        .get("id")
+                        .and_then(|x| x.as_str())
+                        .map(|s| s.to_string())
+                        .unwrap_or_else(|| format!("call_{}", i)),
+                    name: c.pointer("/function/name").and_then(|x| x.as_str()).unwrap_or("").to_string(),
+                    arguments: c.pointer("/function/arguments").cloned().unwrap_or_else(|| json!({})),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn build_body(req: &ChatRequest, model: &str, stream: bool) -> Value {
+    let mut options = serde_json::Map::new();
+    if let Some(t) = req.temperature { options.insert("temperature".into(), json!(t)); }
+    if let Some(k) = req.top_k { options.insert("top_k".into(), json!(k)); }
+    if let Some(p) = req.top_p { options.insert("top_p".into(), json!(p)); }
+    if let Some(p) = req.min_p { options.insert("min_p".into(), json!(p)); }
+    if let Some(n) = req.max_tokens { options.insert("num_predict".into(), json!(n)); }
+    if let Some(n) = req.repeat_last_n { options.insert("repeat_last_n".into(), json!(n)); }
+    if let Some(stop) = req.stop.as_ref().filter(|s| !s.is_empty()) { options.insert("stop".into(), json!(stop)); }
+    if let Some(seed) = req.seed { options.insert("seed".into(), json!(seed)); }
+
+    let messages: Vec<Value> = req.messages.iter().map(message_json).collect();
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "stream": stream,
+        "options": Value::Object(options),
+    });
+    if !req.tool_definitions.is_empty() { body["tools"] = openai::tools_json(&req.tool_definitions); }
+    if let Some(schema) = &req.response_schema { body["format"] = schema.clone(); }
+    if let Some(extra) = &req.extra {
+        merge_json(&mut body, extra);
+    }
+    body
+}
+
+/// Reads the metadata of a final (`done: true`) Ollama chat object.
+fn parse_final(v: &Value, model: &str) -> ChatResponse {
+    let usage = Usage::from_ollama(v);
+    ChatResponse {
+        content: String::new(),
+        role: v
+            .pointer("/message/role")
+            .and_then(|r| r.as_str())
+            .unwrap_or("assistant")
+            .to_string(),
+        finish_reason: v.get("done_reason").and_then(|r| r.as_str()).map(|s| s.to_string()),
+        usage,
+        model: v.get("model").and_then(|m| m.as_str()).unwrap_or(model).to_string(),
+        latency_ms: 0,
+        tool_calls: None,
+        reasoning: None,
+        parsed: None,
+        cached: false,
+    }
+}
+
+/// Ollama streams newline-delimited JSON objects; the last one has `done: true`.
+/// With `think` enabled, reasoning arrives in `message.thinking`.
+async fn read_ndjson_stream(response: reqwest::Response, sink: &StreamSink, model: &str) -> Result<ChatResponse, ProviderError> {
+    let mut content = String::new();
+    let mut reasoning = String::new();
+    let mut tool_calls: Vec<ToolCall> = Vec::new();
+    let mut last: Option<Value> = None;
+    let mut error: Option<ProviderError> = None;
+    let mut buf: Vec<u8> = Vec::new();
+    let mut stream = response.bytes_stream();
+
+    let mut handle_line = |line: &[u8]| {
+        let Ok(v) = serde_json::from_slice::<Value>(line) else { return; };
+        if v.get("error").is_some() {
+            error = Some(ProviderError::from_stream_error(&v));
+            return;
+        }
+        if let Some(token) = v.pointer("/message/content").and_then(|c| c.as_str()) {
+            content.push_str(token);
+            sink.token(token);
+        }
+        if let Some(token) = v.pointer("/message/thinking").and_then(|t| t.as_str()) {
+            reasoning.push_str(token);
+            sink.reasoning(token);
+        }
+        tool_calls.extend(parse_tool_calls(&v));
+        if v.get("done").and_then(|d| d.as_bool()).unwrap_or(false) {
+            last = Some(v);
+        }
+    };
+
+    while let Some(item) = stream.next().await {
+        let chunk = item?;
+        buf.extend_from_slice(&chunk);
+        while let Some(pos) = buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=pos).collect();
+            handle_line(&line);
+        }
+    }
+    if !buf.is_empty() {
+        handle_line(&buf);
+    }
+    if let Some(e) = error {
+        return Err(e);
+    }
+
+    let mut resp = last.as_ref().map(|v| parse_final(v, model)).unwrap_or_else(|| parse_final(&Value::Null, model));
+    resp.content = content;
+    resp.reasoning = Some(reasoning).filter(|r| !r.is_empty());
+    if !tool_calls.is_empty() { resp.tool_calls = Some(tool_calls); }
+    Ok(resp)
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use crate::db::SettingsPayload;
+use crate::streaming::{SseDecoder, StreamSink};
+use crate::tools::{ToolCall, ToolDefinition};
+use super::error::ProviderError;
+use super::http::{send_with_retry, with_headers};
+use super::{fill_if_empty, ChatMessage, ChatRequest, ChatResponse, Provider, Usage};
+
+const DEFAULT_BASE: &str = "https://api.openai.com/v1";
+
+/// Any endpoint that speaks the OpenAI `/chat/completions` protocol.
+pub struct OpenAiProvider;
+
+impl Provider for OpenAiProvider {
+    fn apply_defaults(&self, req: &mut ChatRequest, settings: &SettingsPayload) {
+        fill_if_empty(&mut req.base_url, &settings.api_base);
+        fill_if_empty(&mut req.api_key, &settings.api_key);
+        fill_if_empty(&mut req.model, &settings.api_model);
+    }
+
+    fn chat<'a>(
+        &'a self,
+        req: &'a ChatRequest,
+        sink: Option<&'a StreamSink>,
+    ) -> BoxFuture<'a, Result<ChatResponse, ProviderError>> {
+        Box::pin(async move {
+            let base = req.base_url.as_deref().unwrap_or(DEFAULT_BASE).trim_end_matches('/');
+            let url = format!("{}/chat/completions", base);
+            let model = req.model.clone().unwrap_or_default();
+            let body = build_body(req, &model, sink.is_some());
+            send(&req.http_client(), &url, req.api_key.as_deref(), req.extra_headers.as_ref(), body, sink, &model).await
+        })
+    }
+}
+
+/// Serializes a message, including OpenAI-style tool calls and tool results.
+/// Messages with images use content parts (also understood by llama-server multimodal models).
+pub(crate) fn message_json(m: &ChatMessage) -> Value {
+    let mut v = json!({"role": m.role, "content": m.content});
+    if !m.image_list().is_empty() {
+        let mut parts = vec![json!({"type": "text", "text": m.content})];
+        for image in m.image_list() {
+            parts.push(json!({"type": "image_url", "image_url": {"url": image.data_url()}}));
+        }
+        v["content"] = Value::Array(parts);
+    }
+    if let Some(calls) = &m.tool_calls {
+        v["tool_calls"] = calls
+            .iter()
+            .map(|c| {
+                // OpenAI expects arguments as a JSON-encoded string
+                let arguments = match &c.arguments {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                json!({"id": c.id, "type": "function", "function": {"name": c.name, "arguments": arguments}})
+            })
+            .collect();
+    }
+    if let Some(id) = &m.tool_call_id { v["tool_call_id"] = json!(id); }
+    if let Some(name) = &m.name { v["name"] = json!(name); }
+    v
+}
+
+/// Tool definitions in the OpenAI `tools` format (also accepted by Ollama).
+pub(crate) fn tools_json(tools: &[ToolDefinition]) -> Value {
+    tools
+        .iter()
+        .map(|t| {
+            json!({
+                "type": "function",
+                "function": {"name": t.name, "description": t.description, "parameters": t.parameters}
+            })
+        })
+        .collect()
+}
+
+/// Parses `arguments`, which OpenAI sends as a JSON string; keeps the raw text if it is not valid JSON.
+fn parse_arguments(raw: &str) -> Value {
+    if raw.trim().is_empty() {
+        return json!({});
+    }
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+/// Builds a `/chat/completions` body from the normalized request.
+pub(crate) fn build_body(req: &ChatRequest, model: &str, stream: bool) -> Value {
+    let messages: Vec<Value> = req.messages.iter().map(message_json).collect();
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+    });
+    if !req.tool_definitions.is_empty() { body["tools"] = tools_json(&req.tool_definitions); }
+    if let Some(schema) = &req.response_schema {
+        body["response_format"] = json!({
+            "type": "json_schema",
+            "json_schema": {"name": "response", "schema": schema}
+        });
+    }
+    if let Some(t) = req.temperature { body["temperature"] = json!(t); }
+    if let Some(p) = req.top_p { body["top_p"] = json!(p); }
+    // -1 means "unlimited" in settings; OpenAI rejects non-positive values
+    if let Some(n) = req.max_tokens.filter(|n| *n > 0) { body["max_tokens"] = json!(n); }
+    if let Some(stop) = req.stop.as_ref().filter(|s| !s.is_empty()) { body["stop"] = json!(stop); }
+    if let Some(seed) = req.seed { body["seed"] = json!(seed); }
+    if stream {
+        body["stream"] = json!(true);
+        // Ask for a trailing usage chunk; servers that do not know the option ignore it
+        body["stream_options"] = json!({"include_usage": true});
+    }
+    if let (Some(Value::Object(extra)), Some(map)) = (req.extra.as_ref(), body.as_object_mut()) {
+        for (k, v) in extra {
+            map.insert(k.clone(), v.clone());
+        }
+    }
+    body
+}
+
+/// Posts a `/chat/completions` body and normalizes the (streamed or whole) response.
+pub(crate) async fn send(
+    client: &reqwest::Client,
+    url: &str,
+    api_key: Option<&str>,
+    headers: Option<&HashMap<String, String>>,
+    body: Value,
+    sink: Option<&StreamSink>,
+    model: &str,
+) -> Result<ChatResponse, ProviderError> {
+    let response = send_with_retry(|| {
+        let mut req = with_headers(client.post(url).json(&body), headers);
+        if let Some(key) = api_key.filter(|k| !k.trim().is_empty()) {
+            req = req.header("Authorization", format!("Bearer {}", key));
+        }
+        req
+    })
+    .await?;
+
+    if let Some(sink) = sink {
+        let streamed = read_sse_stream(response, sink).await?;
+        return Ok(ChatResponse {
+            content: streamed.content,
+            role: "assistant".to_string(),
+            finish_reason: streamed.finish_reason,
+            usage: streamed
+                .usage
+                .as_ref()
+                .and_then(Usage::from_openai)
+                .or_else(|| streamed.timings.as_ref().and_then(Usage::from_llama_timings)),
+            model: streamed.model.unwrap_or_else(|| model.to_string()),
+            latency_ms: 0,
+            tool_calls: if streamed.tool_calls.is_empty() { None } else { Some(streamed.tool_calls) },
+            reasoning: Some(streamed.reasoning).filter(|r| !r.is_empty()),
+            parsed: None,
+            cached: false,
+        });
+    }
+
+    let v: Value = response.json().await?;
+    Ok(parse_completion(&v, model))
+}
+
+/// Normalizes a non-streaming `/chat/completions` response body.
+pub(crate) fn parse_completion(v: &Value, model: &str) -> ChatResponse {
+    let choice = v.get("choices").and_then(|c| c.get(0));
+    let message = choice.and_then(|c| c.get("message"));
+    let tool_calls = message
+        .and_then(|m| m.get("tool_calls"))
+        .and_then(|c| c.as_array())
+        .map(|calls| {
+            calls
+                .iter()
+                .enumerate()
+                .map(|(i, c)| ToolCall {
+                    id: c
+                        .get("id")
+                        .and_then(|x| x.as_str())
+                        .map(|s| s.to_string())
+                        .unwrap_or_else(|| format!("call_{}", i)),
+                    name: c.pointer("/function/name").and_then(|x| x.as_str()).unwrap_or("").to_string(),
+                    arguments: match c.pointer("/function/arguments") {
+                        Some(Value::String(raw)) => parse_arguments(raw),
+                        Some(other) => other.clone(),
+                        None => json!({}),
+                    },
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|calls| !calls.is_empty());
+    ChatResponse {
+        content: message
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_str())
+            .unwrap_or("")
+            .to_string(),
+        role: message
+            .and_then(|m| m.get("role"))
+            .and_then(|r| r.as_str())
+            .unwrap_or("assistant")
+            .to_string(),
+        finish_reason: choice
+            .and_then(|c| c.get("finish_reason"))
+            .and_then(|r| r.as_str())
+            .map(|s| s.to_string()),
+        // llama-server may report only `timings`
+        usage: v
+            .get("usage")
+            .and_then(Usage::from_openai)
+            .or_else(|| v.get("timings").and_then(Usage::from_llama_timings)),
+        model: v
+            .get("model")
+            .and_then(|m| m.as_str())
+            .unwrap_or(model)
+            .to_string(),
+        latency_ms: 0,
+        tool_calls,
+        reasoning: message.and_then(reasoning_field).map(|r| r.to_string()),
+        parsed: None,
+        cached: false,
+    }
+}
+
+/// DeepSeek and llama-server report reasoning as `reasoning_content`, some other servers as `reasoning`.
+fn reasoning_field(v: &Value) -> Option<&str> {
+    v.get("reasoning_content")
+        .or_else(|| v.get("reasoning"))
+        .and_then(|r| r.as_str())
+        .filter(|r| !r.is_empty())
+}
+
+pub(crate) struct StreamedCompletion {
+    pub content: String,
+    pub reasoning: String,
+    pub model: Option<String>,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Value>,
+    /// llama-server `timings`, which it may send instead of `usage`.
+    pub timings: Option<Value>,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// Reads an OpenAI-style SSE stream, emitting every content and reasoning delta through `sink`.
+pub(crate) async fn read_sse_stream(response: reqwest::Response, sink: &StreamSink) -> Result<StreamedCompletion, ProviderError> {
+    let mut decoder = SseDecoder::default();
+    let mut out = StreamedCompletion { content: String::new(), reasoning: String::new(), model: None, finish_reason: None, usage: None, timings: None, tool_calls: Vec::new() };
+    // Tool calls arrive in fragments keyed by index: (id, name, arguments so far)
+    let mut partial_calls: Vec<(String, String, String)> = Vec::new();
+    let mut error: Option<ProviderError> = None;
+
+    let mut handle_data = |data: &str| -> bool {
+        if data == "[DONE]" { return true; }
+        let Ok(v) = serde_json::from_str::<Value>(data) else { return false; };
+        // Servers report failures after the stream started as an `error` object
+        if v.get("error").is_some_and(|e| !e.is_null()) {
+            error = Some(ProviderError::from_stream_error(&v));
+            return true;
+        }
+        if out.model.is_none() {
+            out.model = v.get("model").and_then(|m| m.as_str()).map(|s| s.to_string());
+        }
+        if let Some(u) = v.get("usage").filter(|u| !u.is_null()) {
+            out.usage = Some(u.clone());
+        }
+        if let Some(t) = v.get("timings").filter(|t| !t.is_null()) {
+            out.timings = Some(t.clone());
+        }
+        if let Some(choice) = v.get("choices").and_then(|c| c.get(0)) {
+            if let Some(token) = choice.pointer("/delta/content").and_then(|t| t.as_str()) {
+                out.content.push_str(token);
+                sink.token(token);
+            }
+            if let Some(token) = choice.get("delta").and_then(reasoning_field) {
+                out.reasoning.push_str(token);
+                sink.reasoning(token);
+            }
+            if let Some(deltas) = choice.pointer("/delta/tool_calls").and_then(|c| c.as_array()) {
+                for d in deltas {
+                    let idx = d.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as usize;
+                    while partial_calls.len() <= idx {
+                        partial_calls.push((String::new(), String::new(), String::new()));
+                    }
+                    let entry = &mut partial_calls[idx];
+                    if let Some(id) = d.get("id").and_then(|x| x.as_str()) { entry.0 = id.to_string(); }
+                    if let Some(name) = d.pointer("/function/name").and_then(|x| x.as_str()) { entry.1.push_str(name); }
+                    if let Some(args) = d.pointer("/function/arguments").and_then(|x| x.as_str()) { entry.2.push_str(args); }
+                }
+            }
+            if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+                out.finish_reason = Some(reason.to_string());
+            }
+        }
+        false
+    };
+
+    let mut stream = response.bytes_stream();
+    let mut done = false;
+    while let Some(item) = stream.next().await {
+        let chunk = item?;
+        for data in decoder.push(&chunk) {
+            if handle_data(&data) { done = true; break; }
+        }
+        if done { break; }
+    }
+    if !done {
+        if let Some(data) = decoder.finish() {
+            handle_data(&data);
+        }
+    }
+    if let Some(e) = error {
+        return Err(e);
+    }
+    out.tool_calls = partial_calls
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, name, _))| !name.is_empty())
+        .map(|(i, (id, name, args))| ToolCall {
+            id: if id.is_empty() { format!("call_{}", i) } else { id },
+            name,
+            arguments: parse_arguments(&args),
+        })
+        .collect();
+    Ok(out)
+}
@@ -0,0 +1,129 @@
+use serde_json::{json, Value};
+
+const OPEN: &str = "<think>";
+const CLOSE: &str = "</think>";
+
+/// Incremental splitter for inline `<think>…</think>` blocks, as emitted by many local
+/// reasoning models. Tags may be cut across chunks; a possible partial tag is held back
+/// until the next `push`.
+#[derive(Default)]
+pub struct ThinkSplitter {
+    in_think: bool,
+    pending: String,
+    // Drop the whitespace models put between `</think>` and the answer
+    trim_content: bool,
+}
+
+/// Length of the longest suffix of `buf` that is a proper prefix of `tag`.
+fn partial_tag_len(buf: &str, tag: &str) -> usize {
+    (1..tag.len()).rev().find(|&n| buf.ends_with(&tag[..n])).unwrap_or(0)
+}
+
+impl ThinkSplitter {
+    /// Feeds a chunk and returns the `(content, reasoning)` text it completes.
+    pub fn push(&mut self, text: &str) -> (String, String) {
+        let mut buf = std::mem::take(&mut self.pending);
+        buf.push_str(text);
+        let (mut content, mut reasoning) = (String::new(), String::new());
+        loop {
+            let tag = if self.in_think { CLOSE } else { OPEN };
+            match buf.find(tag) {
+                Some(i) => {
+                    self.emit(&buf[..i], &mut content, &mut reasoning);
+                    buf.drain(..i + tag.len());
+                    self.in_think = !self.in_think;
+                    self.trim_content = !self.in_think;
+                }
+                None => {
+                    let split = buf.len() - partial_tag_len(&buf, tag);
+                    self.emit(&buf[..split], &mut content, &mut reasoning);
+                    self.pending = buf[split..].to_string();
+                    return (content, reasoning);
+                }
+            }
+        }
+    }
+
+    /// Flushes text held back as a possible partial tag.
+    pub fn finish(&mut self) -> (String, String) {
+        let rest = std::mem::take(&mut self.pending);
+        let (mut content, mut reasoning) = (String::new(), String::new());
+        self.emit(&rest, &mut content, &mut reasoning);
+        (content, reasoning)
+    }
+
+    fn emit(&mut self, text: &str, content: &mut String, reasoning: &mut String) {
+        if self.in_think {
+            reasoning.push_str(text);
+            return;
+        }
+        let text = if self.trim_content { text.trim_start() } else { text };
+        if !text.is_empty() {
+            self.trim_content = false;
+        }
+        content.push_str(text);
+    }
+}
+
+/// Splits a whole answer into content and inline reasoning. A `</think>` without an
+/// opening tag means the chat template opened the block in the prompt, so everything
+/// before it is reasoning. An unclosed block (answer cut off) is all reasoning.
+pub fn split(text: &str) -> (String, Option<String>) {
+    let close = text.find(CLOSE);
+    let open = text.find(OPEN);
+    if open.is_none() && close.is_none() {
+        return (text.to_string(), None);
+    }
+    let opened_in_prompt = match (open, close) {
+        (Some(o), Some(c)) => c < o,
+        (None, Some(_)) => true,
+        _ => false,
+    };
+    let mut splitter = ThinkSplitter { in_think: opened_in_prompt, ..Default::default() };
+    let (mut content, mut reasoning) = splitter.push(text);
+    let (rest_content, rest_reasoning) = splitter.finish();
+    content.push_str(&rest_content);
+    reasoning.push_str(&rest_reasoning);
+    let reasoning = reasoning.trim().to_string();
+    (content, Some(reasoning).filter(|r| !r.is_empty()))
+}
+
+/// Appends `more` to reasoning reported separately by the provider.
+pub fn join(reasoning: Option<String>, more: Option<String>) -> Option<String> {
+    match (reasoning.filter(|r| !r.is_empty()), more) {
+        (Some(a), Some(b)) => Some(format!("{}\n{}", a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Removes reasoning from an assistant turn of the history; models should not see
+/// their earlier thoughts (DeepSeek rejects them outright).
+pub fn strip_history(role: &str, content: &mut String) {
+    if role == "assistant" && (content.contains(OPEN) || content.contains(CLOSE)) {
+        *content = split(content).0;
+    }
+}
+
+/// `strip_history` for messages given as raw JSON (`[{role, content}]`).
+pub fn strip_history_json(messages: &mut Value) {
+    for m in messages.as_array_mut().into_iter().flatten() {
+        let role = m.get("role").and_then(|r| r.as_str()).unwrap_or("").to_string();
+        if let Some(Value::String(content)) = m.get_mut("content") {
+            strip_history(&role, content);
+        }
+    }
+}
+
+/// Moves inline reasoning of a raw response body into `field` of the message at
+/// `message` (`reasoning_content` for OpenAI-style bodies, `thinking` for Ollama).
+/// Bodies that are not JSON or have no reasoning are returned unchanged.
+pub fn split_raw(raw: String, message: &str, field: &str) -> String {
+    let Ok(mut v) = serde_json::from_str::<Value>(&raw) else { return raw; };
+    let Some(m) = v.pointer_mut(message) else { return raw; };
+    let Some(text) = m.get("content").and_then(|c| c.as_str()) else { return raw; };
+    let (content, Some(inline)) = split(text) else { return raw; };
+    let existing = m.get(field).and_then(|r| r.as_str()).map(|r| r.to_string());
+    m["content"] = json!(content);
+    m[field] = json!(join(existing, Some(inline)));
+    v.to_string()
+}
@@ -0,0 +1,290 @@
+use std::future::Future;
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::providers::error::ProviderError;
+
+/// One place where a value does not satisfy the schema.
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaViolation {
+    // JSON pointer-like path, "$" is the root
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StructuredOutputError {
+    /// The model did not return parseable JSON.
+    InvalidJson { message: String, raw: String },
+    /// The JSON parsed but does not match `response_schema`.
+    SchemaMismatch { violations: Vec<SchemaViolation>, raw: String },
+}
+
+impl std::fmt::Display for StructuredOutputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StructuredOutputError::InvalidJson { message, .. } => {
+                write!(f, "Structured output is not valid JSON: {}", message)
+            }
+            StructuredOutputError::SchemaMismatch { violations, .. } => {
+                let list: Vec<String> = violations.iter().map(|v| format!("{}: {}", v.path, v.message)).collect();
+                write!(f, "Structured output does not match schema: {}", list.join("; "))
+            }
+        }
+    }
+}
+
+impl StructuredOutputError {
+    pub fn raw(&self) -> &str {
+        match self {
+            StructuredOutputError::InvalidJson { raw, .. } => raw,
+            StructuredOutputError::SchemaMismatch { raw, .. } => raw,
+        }
+    }
+
+    /// Follow-up user message asking the model to fix its previous answer.
+    pub fn repair_prompt(&self) -> String {
+        format!(
+            "Your previous reply was rejected. {}. Reply again with only the corrected JSON, without code fences or commentary.",
+            self
+        )
+    }
+}
+
+/// Instruction for providers without a native schema option.
+pub fn instruction(schema: &Value) -> String {
+    format!("Respond only with a JSON value that matches this JSON schema:\n{}", schema)
+}
+
+/// Parses model output as JSON (tolerating a surrounding ``` fence) and validates it against `schema`.
+pub fn parse_and_validate(schema: &Value, text: &str) -> Result<Value, StructuredOutputError> {
+    let value: Value = serde_json::from_str(strip_code_fence(text)).map_err(|e| StructuredOutputError::InvalidJson {
+        message: e.to_string(),
+        raw: text.to_string(),
+    })?;
+    let violations = validate(schema, &value);
+    if violations.is_empty() {
+        Ok(value)
+    } else {
+        Err(StructuredOutputError::SchemaMismatch { violations, raw: text.to_string() })
+    }
+}
+
+fn strip_code_fence(text: &str) -> &str {
+    let t = text.trim();
+    let Some(rest) = t.strip_prefix("```") else { return t; };
+    let Some(rest) = rest.strip_suffix("```") else { return t; };
+    // Drop the language tag line ("```json")
+    match rest.find('\n') {
+        Some(i) => rest[i + 1..].trim(),
+        None => rest.trim(),
+    }
+}
+
+/// Validates `instance` against `schema`. Supports the keywords structured-output
+/// APIs accept: type, enum, const, properties, required, additionalProperties, items,
+/// length/size/range limits, allOf/anyOf/oneOf and local `$ref`s. Other keywords are ignored.
+pub fn validate(schema: &Value, instance: &Value) -> Vec<SchemaViolation> {
+    let mut out = Vec::new();
+    check(schema, schema, instance, "$", &mut out);
+    out
+}
+
+fn violation(out: &mut Vec<SchemaViolation>, path: &str, message: String) {
+    out.push(SchemaViolation { path: path.to_string(), message });
+}
+
+fn type_matches(ty: &str, v: &Value) -> bool {
+    match ty {
+        "object" => v.is_object(),
+        "array" => v.is_array(),
+        "string" => v.is_string(),
+        "boolean" => v.is_boolean(),
+        "null" => v.is_null(),
+        "number" => v.is_number(),
+        "integer" => v.as_i64().is_some() || v.as_u64().is_some() || v.as_f64().map(|f| f.fract() == 0.0).unwrap_or(false),
+        _ => true,
+    }
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    if pointer.is_empty() { return Some(root); }
+    root.pointer(pointer)
+}
+
+fn check(root: &Value, schema: &Value, v: &Value, path: &str, out: &mut Vec<SchemaViolation>) {
+    // `true`/`false` are valid schemas
+    let Some(s) = schema.as_object() else {
+        if schema == &Value::Bool(false) {
+            violation(out, path, "no value is allowed here".to_string());
+        }
+        return;
+    };
+
+    if let Some(reference) = s.get("$ref").and_then(|r| r.as_str()) {
+        match resolve(root, reference) {
+            Some(target) => check(root, target, v, path, out),
+            None => violation(out, path, format!("unresolvable $ref '{}'", reference)),
+        }
+    }
+
+    if let Some(ty) = s.get("type") {
+        let allowed: Vec<&str> = match ty {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(list) => list.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| type_matches(t, v)) {
+            violation(out, path, format!("expected {}, got {}", allowed.join(" or "), type_name(v)));
+            return;
+        }
+    }
+
+    if let Some(options) = s.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(v) {
+            violation(out, path, format!("must be one of {}", Value::Array(options.clone())));
+        }
+    }
+    if let Some(c) = s.get("const") {
+        if c != v {
+            violation(out, path, format!("must equal {}", c));
+        }
+    }
+
+    match v {
+        Value::Object(map) => {
+            let props = s.get("properties").and_then(|p| p.as_object());
+            if let Some(required) = s.get("required").and_then(|r| r.as_array()) {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !map.contains_key(key) {
+                        violation(out, path, format!("missing required property '{}'", key));
+                    }
+                }
+            }
+            for (key, value) in map {
+                let child = format!("{}.{}", path, key);
+                match props.and_then(|p| p.get(key)) {
+                    Some(prop) => check(root, prop, value, &child, out),
+                    None => match s.get("additionalProperties") {
+                        Some(Value::Bool(false)) => violation(out, path, format!("unexpected property '{}'", key)),
+                        Some(extra @ Value::Object(_)) => check(root, extra, value, &child, out),
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(n) = s.get("minItems").and_then(|n| n.as_u64()) {
+                if (items.len() as u64) < n { violation(out, path, format!("expected at least {} items", n)); }
+            }
+            if let Some(n) = s.get("maxItems").and_then(|n| n.as_u64()) {
+                if (items.len() as u64) > n { violation(out, path, format!("expected at most {} items", n)); }
+            }
+            if let Some(item_schema) = s.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(root, item_schema, item, &format!("{}[{}]", path, i), out);
+                }
+            }
+        }
+        Value::String(text) => {
+            let len = text.chars().count() as u64;
+            if let Some(n) = s.get("minLength").and_then(|n| n.as_u64()) {
+                if len < n { violation(out, path, format!("shorter than {} characters", n)); }
+            }
+            if let Some(n) = s.get("maxLength").and_then(|n| n.as_u64()) {
+                if len > n { violation(out, path, format!("longer than {} characters", n)); }
+            }
+        }
+        Value::Number(num) => {
+            let x = num.as_f64().unwrap_or(0.0);
+            let limit = |key: &str| s.get(key).and_then(|n| n.as_f64());
+            if let Some(m) = limit("minimum") {
+                if x < m { violation(out, path, format!("must be >= {}", m)); }
+            }
+            if let Some(m) = limit("maximum") {
+                if x > m { violation(out, path, format!("must be <= {}", m)); }
+            }
+            if let Some(m) = limit("exclusiveMinimum") {
+                if x <= m { violation(out, path, format!("must be > {}", m)); }
+            }
+            if let Some(m) = limit("exclusiveMaximum") {
+                if x >= m { violation(out, path, format!("must be < {}", m)); }
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(all) = s.get("allOf").and_then(|a| a.as_array()) {
+        for sub in all {
+            check(root, sub, v, path, out);
+        }
+    }
+    if let Some(any) = s.get("anyOf").and_then(|a| a.as_array()) {
+        if !any.iter().any(|sub| validate_with_root(root, sub, v)) {
+            violation(out, path, "does not match any of the anyOf schemas".to_string());
+        }
+    }
+    if let Some(one) = s.get("oneOf").and_then(|a| a.as_array()) {
+        let matched = one.iter().filter(|sub| validate_with_root(root, sub, v)).count();
+        if matched != 1 {
+            violation(out, path, format!("must match exactly one oneOf schema, matched {}", matched));
+        }
+    }
+}
+
+fn validate_with_root(root: &Value, schema: &Value, v: &Value) -> bool {
+    let mut out = Vec::new();
+    check(root, schema, v, "$", &mut out);
+    out.is_empty()
+}
+
+/// Sends a chat-completions style `body` through `send` and validates the reply text found at
+/// `content_pointer`. On mismatch the rejected answer and a repair prompt are appended to
+/// `body["messages"]` and the request is sent again, up to `repair_attempts` times.
+/// `send` gets the attempt number (0 for the first request). Returns the raw response text.
+pub async fn send_validated<F, Fut>(
+    mut body: Value,
+    schema: &Value,
+    content_pointer: &str,
+    repair_attempts: u32,
+    mut send: F,
+) -> Result<String, ProviderError>
+where
+    F: FnMut(Value, u32) -> Fut,
+    Fut: Future<Output = Result<String, ProviderError>>,
+{
+    let mut attempt = 0;
+    loop {
+        let raw = send(body.clone(), attempt).await?;
+        let parsed: Value = serde_json::from_str(&raw).map_err(|e| ProviderError::InvalidResponse { message: e.to_string() })?;
+        // Error bodies have no message content to validate
+        let Some(content) = parsed.pointer(content_pointer).and_then(|c| c.as_str()) else {
+            return Err(ProviderError::InvalidResponse { message: format!("Unexpected response: {}", raw) });
+        };
+        let err = match parse_and_validate(schema, content) {
+            Ok(_) => return Ok(raw),
+            Err(e) => e,
+        };
+        println!("[schema.send_validated] attempt {} rejected: {}", attempt, err);
+        if attempt >= repair_attempts {
+            return Err(err.into());
+        }
+        if let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) {
+            messages.push(json!({"role": "assistant", "content": err.raw()}));
+            messages.push(json!({"role": "user", "content": err.repair_prompt()}));
+        }
+        attempt += 1;
+    }
+}
@@ -0,0 +1,152 @@
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use tauri::Manager;
+
+/// Prefix of encrypted values: `enc:v1:` + base64(nonce || ciphertext).
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+const KEY_FILE: &str = "secret.key";
+/// What the frontend sees instead of a stored key. Saving it back keeps the stored key.
+pub const MASK: &str = "••••••••";
+
+static KEY: OnceLock<Key<Aes256Gcm>> = OnceLock::new();
+
+fn key_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("app_data_dir error: {}", e))?;
+    Ok(dir.join(KEY_FILE))
+}
+
+/// Reads the key file; `None` when it does not exist yet.
+fn read_key(path: &Path) -> Result<Option<Key<Aes256Gcm>>, String> {
+    match std::fs::read(path) {
+        Ok(bytes) if bytes.len() == 32 => Ok(Some(*Key::<Aes256Gcm>::from_slice(&bytes))),
+        Ok(bytes) => Err(format!("Key file '{}' is corrupt ({} bytes, expected 32)", path.display(), bytes.len())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Cannot read key file '{}': {}", path.display(), e)),
+    }
+}
+
+/// Reads the per-install key, creating it (readable by the owner only) on first use.
+fn load_or_create_key(app: &tauri::AppHandle) -> Result<Key<Aes256Gcm>, String> {
+    let path = key_path(app)?;
+    if let Some(key) = read_key(&path)? {
+        return Ok(key);
+    }
+
+    println!("[secrets.load_or_create_key] Creating key file at {}", path.display());
+    if let Some(parent) = path.parent() { std::fs::create_dir_all(parent).map_err(|e| e.to_string())?; }
+    let key = Aes256Gcm::generate_key(OsRng);
+    // The key is written to a private temp file and linked into place, so the key file
+    // never exists half-written, even after a crash
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let tmp = path.with_extension(format!("key.{}.{}.tmp", std::process::id(), nanos));
+    let written = write_key_file(&tmp, &key).and_then(|_| match std::fs::hard_link(&tmp, &path) {
+        Ok(()) => Ok(None),
+        // Another thread or instance got there first: use its key
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => read_key(&path),
+        Err(e) => Err(format!("Cannot create key file '{}': {}", path.display(), e)),
+    });
+    let _ = std::fs::remove_file(&tmp);
+    Ok(written?.unwrap_or(key))
+}
+
+fn write_key_file(path: &Path, key: &Key<Aes256Gcm>) -> Result<(), String> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| format!("Cannot create key file '{}': {}", path.display(), e))?;
+    #[cfg(windows)]
+    restrict_to_owner(path)?;
+    file.write_all(key.as_slice()).map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())
+}
+
+/// Windows has no mode bits: drop the inherited ACL entries and grant access to the owner only.
+#[cfg(windows)]
+fn restrict_to_owner(path: &Path) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    // S-1-3-4 is OWNER RIGHTS
+    let status = std::process::Command::new("icacls")
+        .arg(path)
+        .args(["/inheritance:r", "/grant:r", "*S-1-3-4:F"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .status()
+        .map_err(|e| format!("Cannot run icacls: {}", e))?;
+    if !status.success() {
+        return Err(format!("Cannot restrict access to key file '{}': icacls {}", path.display(), status));
+    }
+    Ok(())
+}
+
+fn cipher(app: &tauri::AppHandle) -> Result<Aes256Gcm, String> {
+    if let Some(key) = KEY.get() {
+        return Ok(Aes256Gcm::new(key));
+    }
+    let key = load_or_create_key(app)?;
+    Ok(Aes256Gcm::new(KEY.get_or_init(|| key)))
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// Whether `value` is the placeholder sent to the frontend rather than a real key.
+pub fn is_mask(value: &str) -> bool {
+    value == MASK
+}
+
+/// Placeholder for a stored secret: empty stays empty so the UI can tell "no key" from "key set".
+pub fn mask(stored: &str) -> String {
+    if stored.is_empty() { String::new() } else { MASK.to_string() }
+}
+
+/// Encrypts a secret for storage. Empty and already encrypted values are returned unchanged.
+pub fn encrypt(app: &tauri::AppHandle, plain: &str) -> Result<String, String> {
+    if plain.is_empty() || is_encrypted(plain) {
+        return Ok(plain.to_string());
+    }
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(app)?
+        .encrypt(&nonce, plain.as_bytes())
+        .map_err(|_| "Failed to encrypt secret".to_string())?;
+    let mut out = nonce.to_vec();
+    out.extend(ciphertext);
+    Ok(format!("{}{}", PREFIX, BASE64.encode(out)))
+}
+
+/// Decrypts a stored secret. Values without the prefix (not yet migrated) are returned as-is.
+pub fn decrypt(app: &tauri::AppHandle, stored: &str) -> Result<String, String> {
+    let Some(encoded) = stored.strip_prefix(PREFIX) else {
+        return Ok(stored.to_string());
+    };
+    let bytes = BASE64.decode(encoded).map_err(|e| format!("Corrupt encrypted secret: {}", e))?;
+    if bytes.len() <= NONCE_LEN {
+        return Err("Corrupt encrypted secret: too short".to_string());
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let plain = cipher(app)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Cannot decrypt secret: the key file changed or the value is corrupt".to_string())?;
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+/// Random 256-bit token, hex encoded (bearer tokens for the local gateway).
+pub fn random_token() -> String {
+    use aes_gcm::aead::rand_core::RngCore;
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
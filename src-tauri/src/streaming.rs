@@ -0,0 +1,188 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::Emitter;
+use tokio::sync::mpsc::UnboundedSender;
+use crate::providers::error::ProviderError;
+use crate::reasoning::ThinkSplitter;
+
+/// Event emitted for every generated token chunk.
+pub const STREAM_TOKEN_EVENT: &str = "chat_stream_token";
+/// Event emitted for every chunk of reasoning ("thinking") text, kept apart from the answer.
+pub const STREAM_REASONING_EVENT: &str = "chat_stream_reasoning";
+/// Event emitted once the stream is finished, successfully or not.
+pub const STREAM_DONE_EVENT: &str = "chat_stream_done";
+
+#[derive(Clone, Serialize)]
+pub struct StreamTokenPayload {
+    pub request_id: String,
+    pub token: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct StreamDonePayload {
+    pub request_id: String,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Value>,
+    /// Set when the generation failed; `finish_reason` is then `"error"`.
+    pub error: Option<ProviderError>,
+}
+
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the caller's request id, or a fresh one when it is missing or blank.
+pub fn ensure_request_id(request_id: Option<String>) -> String {
+    request_id
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(new_request_id)
+}
+
+/// Generates a request id for callers that did not supply their own.
+pub fn new_request_id() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let seq = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("req-{}-{}", millis, seq)
+}
+
+/// A streamed event delivered to an in-process consumer instead of the webview.
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    Token(String),
+    Reasoning(String),
+    Done { finish_reason: Option<String>, usage: Option<Value>, error: Option<ProviderError> },
+}
+
+/// Where a sink delivers its events: the webview window, or a channel (the HTTP gateway).
+/// `Discard` drops them; it is used where a backend is streamed only so that an abort
+/// closes the connection while the server is still decoding.
+#[derive(Clone)]
+pub enum StreamTarget {
+    Window(tauri::Window),
+    Channel(UnboundedSender<StreamEvent>),
+    Discard,
+}
+
+/// Emits token, reasoning and done events for one streamed generation.
+/// Inline `<think>` blocks in tokens are routed to the reasoning event.
+pub struct StreamSink {
+    target: StreamTarget,
+    request_id: String,
+    splitter: Mutex<ThinkSplitter>,
+}
+
+impl StreamSink {
+    pub fn new(window: tauri::Window, request_id: Option<String>) -> Self {
+        Self::with_target(StreamTarget::Window(window), request_id)
+    }
+
+    pub fn with_target(target: StreamTarget, request_id: Option<String>) -> Self {
+        Self { target, request_id: ensure_request_id(request_id), splitter: Mutex::new(ThinkSplitter::default()) }
+    }
+
+    /// A sink nobody listens to.
+    pub fn discard() -> Self {
+        Self::with_target(StreamTarget::Discard, None)
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    pub fn token(&self, token: &str) {
+        let (content, reasoning) = match self.splitter.lock() {
+            Ok(mut splitter) => splitter.push(token),
+            Err(_) => (token.to_string(), String::new()),
+        };
+        self.reasoning(&reasoning);
+        self.emit_token(false, &content);
+    }
+
+    /// Reasoning the provider reports separately (`reasoning_content`, `thinking`).
+    pub fn reasoning(&self, token: &str) {
+        self.emit_token(true, token);
+    }
+
+    fn emit_token(&self, reasoning: bool, token: &str) {
+        if token.is_empty() { return; }
+        match &self.target {
+            StreamTarget::Window(window) => {
+                let event = if reasoning { STREAM_REASONING_EVENT } else { STREAM_TOKEN_EVENT };
+                let _ = window.emit(event, StreamTokenPayload { request_id: self.request_id.clone(), token: token.to_string() });
+            }
+            StreamTarget::Channel(tx) => {
+                let _ = tx.send(if reasoning { StreamEvent::Reasoning(token.to_string()) } else { StreamEvent::Token(token.to_string()) });
+            }
+            StreamTarget::Discard => {}
+        }
+    }
+
+    pub fn done(&self, finish_reason: Option<String>, usage: Option<Value>) {
+        self.finish(finish_reason, usage, None);
+    }
+
+    /// Final event of a failed generation, so listeners are not left waiting for `done`.
+    /// A cancelled one finishes with `finish_reason: "cancelled"` and no error.
+    pub fn fail(&self, error: &ProviderError) {
+        match error {
+            ProviderError::Cancelled => self.finish(Some("cancelled".to_string()), None, None),
+            e => self.finish(Some("error".to_string()), None, Some(e.clone())),
+        }
+    }
+
+    fn finish(&self, finish_reason: Option<String>, usage: Option<Value>, error: Option<ProviderError>) {
+        if let Ok(mut splitter) = self.splitter.lock() {
+            let (content, reasoning) = splitter.finish();
+            self.reasoning(&reasoning);
+            self.emit_token(false, &content);
+        }
+        match &self.target {
+            StreamTarget::Window(window) => {
+                let _ = window.emit(
+                    STREAM_DONE_EVENT,
+                    StreamDonePayload { request_id: self.request_id.clone(), finish_reason, usage, error },
+                );
+            }
+            StreamTarget::Channel(tx) => {
+                let _ = tx.send(StreamEvent::Done { finish_reason, usage, error });
+            }
+            StreamTarget::Discard => {}
+        }
+    }
+}
+
+/// Incremental Server-Sent Events decoder.
+/// Feed raw body chunks with `push` and get back the complete `data:` payloads.
+#[derive(Default)]
+pub struct SseDecoder {
+    buf: Vec<u8>,
+}
+
+impl SseDecoder {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(chunk);
+        let mut out = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if let Some(data) = line.strip_prefix("data:") {
+                out.push(data.trim_start().to_string());
+            }
+        }
+        out
+    }
+
+    /// Returns a trailing `data:` payload if the body did not end with a newline.
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buf);
+        let line = String::from_utf8_lossy(&rest);
+        line.trim()
+            .strip_prefix("data:")
+            .map(|d| d.trim_start().to_string())
+    }
+}
@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use super::ToolDefinition;
+
+/// Tools implemented in the backend itself.
+pub fn definitions() -> Vec<ToolDefinition> {
+    vec![
+        ToolDefinition {
+            name: "get_current_time".to_string(),
+            description: "Returns the current date and time in UTC.".to_string(),
+            parameters: json!({"type": "object", "properties": {}}),
+        },
+        ToolDefinition {
+            name: "scan_context_folder".to_string(),
+            description: "Reads the text files of the user's context folder and returns their contents.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "Subfolder to read, relative to the context folder; the whole folder when omitted"}
+                }
+            }),
+        },
+    ]
+}
+
+/// Runs a built-in tool. Returns `None` when `name` is not a built-in.
+pub async fn execute(app: &tauri::AppHandle, name: &str, args: &Value) -> Option<Result<Value, String>> {
+    match name {
+        "get_current_time" => Some(Ok(current_time())),
+        "scan_context_folder" => {
+            let path = args.get("path").and_then(|p| p.as_str()).unwrap_or("");
+            Some(context_path(app, path).and_then(|dir| {
+                crate::context::scan_context_folder(dir.to_string_lossy().into_owned(), None, None, None).map(Value::String)
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Resolves `path` inside the `context_folder` setting. The model chooses the path,
+/// so anything outside the folder the user configured is refused.
+fn context_path(app: &tauri::AppHandle, path: &str) -> Result<PathBuf, String> {
+    let settings = crate::db::load_stored_settings(app)?;
+    let folder = settings.context_folder.unwrap_or_default();
+    if folder.trim().is_empty() {
+        return Err("No context folder is configured".to_string());
+    }
+    let root = Path::new(folder.trim())
+        .canonicalize()
+        .map_err(|e| format!("Context folder is not available: {}", e))?;
+    let dir = root
+        .join(path.trim())
+        .canonicalize()
+        .map_err(|e| format!("Folder '{}' is not available: {}", path, e))?;
+    if !dir.starts_with(&root) {
+        return Err(format!("'{}' is outside the context folder", path));
+    }
+    Ok(dir)
+}
+
+fn current_time() -> Value {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (y, m, d) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    json!({
+        "unix": secs,
+        "utc": format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, m, d, rem / 3600, (rem % 3600) / 60, rem % 60),
+    })
+}
+
+/// Converts days since 1970-01-01 into a (year, month, day) civil date.
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{Emitter, Manager};
+use tokio::sync::oneshot;
+use crate::plugins::PluginManager;
+
+pub mod builtin;
+
+/// Event asking a plugin's frontend to run one of its tools.
+pub const PLUGIN_TOOL_CALL_EVENT: &str = "plugin_tool_call";
+const PLUGIN_TOOL_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn empty_schema() -> Value {
+    json!({"type": "object", "properties": {}})
+}
+
+/// A function the model may call, described by a JSON schema.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "empty_schema")]
+    pub parameters: Value,
+}
+
+/// A tool invocation requested by the model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum ToolSource {
+    Builtin,
+    Plugin(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolInfo {
+    #[serde(flatten)]
+    pub definition: ToolDefinition,
+    pub source: ToolSource,
+}
+
+#[derive(Clone, Serialize)]
+struct PluginToolCallPayload {
+    call_id: String,
+    plugin_id: String,
+    name: String,
+    arguments: Value,
+}
+
+/// Plugin tool calls waiting for `tools_submit_result`, keyed by call id.
+#[derive(Default)]
+pub struct ToolState(pub Mutex<HashMap<String, oneshot::Sender<Result<Value, String>>>>);
+
+/// Built-in tools plus the tools declared by enabled plugins in `metadata.json` (`backend.tools`).
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<ToolInfo>,
+}
+
+impl ToolRegistry {
+    pub fn load(app: &tauri::AppHandle) -> Result<Self, String> {
+        let mut registry = ToolRegistry::default();
+        for def in builtin::definitions() {
+            registry.register(def, ToolSource::Builtin);
+        }
+
+        let dir = app.path().app_data_dir().map_err(|e| e.to_string())?.join("plugins");
+        let mut manager = PluginManager::new(dir)?;
+        manager.load_all()?;
+        let mut plugins: Vec<_> = manager.plugins.values().filter(|p| p.enabled).collect();
+        plugins.sort_by(|a, b| a.meta.id.cmp(&b.meta.id));
+        for plugin in plugins {
+            if let Some(backend) = &plugin.meta.backend {
+                for def in &backend.tools {
+                    registry.register(def.clone(), ToolSource::Plugin(plugin.meta.id.clone()));
+                }
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Adds a tool. The first registration of a name wins, so plugins cannot shadow built-ins.
+    pub fn register(&mut self, definition: ToolDefinition, source: ToolSource) {
+        if self.tools.iter().any(|t| t.definition.name == definition.name) {
+            println!("[tools.register] duplicate tool '{}' ignored", definition.name);
+            return;
+        }
+        self.tools.push(ToolInfo { definition, source });
+    }
+
+    pub fn list(&self) -> Vec<ToolInfo> {
+        self.tools.clone()
+    }
+
+    /// Resolves tool names to definitions, failing on unknown names.
+    pub fn definitions(&self, names: &[String]) -> Result<Vec<ToolDefinition>, String> {
+        names
+            .iter()
+            .map(|n| {
+                self.tools
+                    .iter()
+                    .find(|t| &t.definition.name == n)
+                    .map(|t| t.definition.clone())
+                    .ok_or_else(|| format!("Unknown tool '{}'", n))
+            })
+            .collect()
+    }
+
+    /// Runs a tool call and returns the text sent back to the model.
+    /// Failures are reported to the model as text so it can recover.
+    pub async fn execute(&self, app: &tauri::AppHandle, call: &ToolCall) -> String {
+        println!("[tools.execute] name='{}', id='{}'", call.name, call.id);
+        let result = match self.tools.iter().find(|t| t.definition.name == call.name).map(|t| &t.source) {
+            Some(ToolSource::Builtin) => builtin::execute(app, &call.name, &call.arguments)
+                .await
+                .unwrap_or_else(|| Err(format!("Built-in tool '{}' is not implemented", call.name))),
+            Some(ToolSource::Plugin(plugin_id)) => call_plugin_tool(app, plugin_id, call).await,
+            None => Err(format!("Unknown tool '{}'", call.name)),
+        };
+        match result {
+            Ok(Value::String(s)) => s,
+            Ok(v) => v.to_string(),
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+}
+
+/// Plugin tools run in the plugin's frontend: emit a request and wait for `tools_submit_result`.
+async fn call_plugin_tool(app: &tauri::AppHandle, plugin_id: &str, call: &ToolCall) -> Result<Value, String> {
+    let call_id = format!("tool-{}", crate::streaming::new_request_id());
+    let (tx, rx) = oneshot::channel();
+    app.state::<ToolState>()
+        .0
+        .lock()
+        .map_err(|_| "lock poisoned")?
+        .insert(call_id.clone(), tx);
+
+    let _ = app.emit(
+        PLUGIN_TOOL_CALL_EVENT,
+        PluginToolCallPayload {
+            call_id: call_id.clone(),
+            plugin_id: plugin_id.to_string(),
+            name: call.name.clone(),
+            arguments: call.arguments.clone(),
+        },
+    );
+
+    match tokio::time::timeout(PLUGIN_TOOL_TIMEOUT, rx).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => Err(format!("Plugin tool '{}' was dropped", call.name)),
+        Err(_) => {
+            if let Ok(mut pending) = app.state::<ToolState>().0.lock() {
+                pending.remove(&call_id);
+            }
+            Err(format!("Plugin tool '{}' timed out", call.name))
+        }
+    }
+}
+
+#[tauri::command]
+pub fn tools_list(app: tauri::AppHandle) -> Result<Vec<ToolInfo>, String> {
+    Ok(ToolRegistry::load(&app)?.list())
+}
+
+#[tauri::command]
+pub fn tools_submit_result(
+    state: tauri::State<ToolState>,
+    call_id: String,
+    result: Option<Value>,
+    error: Option<String>,
+) -> Result<(), String> {
+    let sender = state.0.lock().map_err(|_| "lock poisoned")?.remove(&call_id);
+    let Some(sender) = sender else {
+        return Err(format!("No pending tool call '{}'", call_id));
+    };
+    let outcome = match error {
+        Some(e) => Err(e),
+        None => Ok(result.unwrap_or(Value::Null)),
+    };
+    let _ = sender.send(outcome);
+    Ok(())
+}
@@ -38,6 +38,25 @@ pub fn get_runtime_dir(app: &tauri::AppHandle, server: &str, variant: &str) -> R
     Ok(dir.join(format!("runtime/{}/{}/", server, variant)))
 }
 
+/// File name of the llama-server executable on the current OS.
+pub fn llama_server_binary() -> &'static str {
+    if cfg!(windows) { "llama-server.exe" } else { "llama-server" }
+}
+
+/// Adds the executable bits to an extracted binary (archives do not always keep them). No-op on Windows.
+pub fn set_executable(path: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = std::fs::metadata(path).map_err(|e| e.to_string())?.permissions();
+        perms.set_mode(perms.mode() | 0o755);
+        std::fs::set_permissions(path, perms).map_err(|e| format!("Cannot make '{}' executable: {}", path.display(), e))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
 /// Recursively find first path with any of provided file names up to given depth.
 pub fn find_first_with_names(dir: &Path, names: &[&str], depth: u8) -> Option<PathBuf> {
     if depth == 0 || !dir.exists() { return None; }
@@ -88,6 +88,8 @@ function App() {
         projects,
         activeProjectId,
         sendMessage,
+        pendingRequestId,
+        stopGeneration,
         downloadStatus,
         persistSettings,
     } = useChatStore();
@@ -319,6 +321,9 @@ function App() {
                         placeholder={(chatMode === 'local' || chatMode === 'ollama') && !isServerReady ?
                             t('chat.startingServer')! : t('chat.sendingPlaceholder')!}
                         sendLabel={t('chat.send')!}
+                        busy={!!pendingRequestId}
+                        onStop={stopGeneration}
+                        stopLabel={t('chat.stop')!}
                     />
                 </ChatFooter>
             )}
@@ -62,7 +62,7 @@ function a11yProps(index: number) {
 export function Settings({ setActiveTab }: SettingsProps) {
     const {
         // API provider settings
-        apiKey, setApiKey,
+        apiKey, apiKeySet, setApiKey,
         apiBase, setApiBase,
         apiModel, setApiModel,
         deepseekUrl, setDeepseekUrl,
@@ -183,6 +183,7 @@ export function Settings({ setActiveTab }: SettingsProps) {
                                 {mode === 'deepseek' && (
                                     <DeepSeekForm
                                         apiKey={apiKey}
+                                        apiKeySet={apiKeySet}
                                         setApiKey={setApiKey}
                                         deepseekUrl={deepseekUrl}
                                         setDeepseekUrl={setDeepseekUrl}
@@ -198,6 +199,7 @@ export function Settings({ setActiveTab }: SettingsProps) {
                                         apiModel={apiModel}
                                         setApiModel={setApiModel}
                                         apiKey={apiKey}
+                                        apiKeySet={apiKeySet}
                                         setApiKey={setApiKey}
                                     />
                                 )}
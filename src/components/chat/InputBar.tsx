@@ -8,9 +8,13 @@ interface InputBarProps {
   disabled?: boolean;
   placeholder?: string;
   sendLabel?: string;
+  // While a reply is being generated the send button turns into Stop
+  busy?: boolean;
+  onStop?: () => void;
+  stopLabel?: string;
 }
 
-export const InputBar: React.FC<InputBarProps> = ({ input, setInput, onSend, disabled, placeholder, sendLabel }) => {
+export const InputBar: React.FC<InputBarProps> = ({ input, setInput, onSend, disabled, placeholder, sendLabel, busy, onStop, stopLabel }) => {
   return (
     <Stack direction="row" spacing={1.5} alignItems="center">
       <TextField
@@ -24,7 +28,7 @@ export const InputBar: React.FC<InputBarProps> = ({ input, setInput, onSend, dis
         onKeyDown={(e) => {
           if (e.key === 'Enter' && !e.shiftKey) {
             e.preventDefault();
-            onSend();
+            if (!busy) onSend();
           }
         }}
         disabled={disabled}
@@ -36,16 +40,28 @@ export const InputBar: React.FC<InputBarProps> = ({ input, setInput, onSend, dis
           }
         }}
       />
-      <Button
-        variant="contained"
-        disableElevation
-        onClick={onSend}
-        disabled={!input.trim() || disabled}
-        sx={{ px: 2.5, borderRadius: 2 }}
-        aria-label={sendLabel ?? 'Отправить'}
-      >
-        {sendLabel ?? 'Отправить'}
-      </Button>
+      {busy ? (
+        <Button
+          variant="outlined"
+          color="error"
+          onClick={onStop}
+          sx={{ px: 2.5, borderRadius: 2 }}
+          aria-label={stopLabel ?? 'Остановить'}
+        >
+          {stopLabel ?? 'Остановить'}
+        </Button>
+      ) : (
+        <Button
+          variant="contained"
+          disableElevation
+          onClick={onSend}
+          disabled={!input.trim() || disabled}
+          sx={{ px: 2.5, borderRadius: 2 }}
+          aria-label={sendLabel ?? 'Отправить'}
+        >
+          {sendLabel ?? 'Отправить'}
+        </Button>
+      )}
     </Stack>
   );
 };
\ No newline at end of file
@@ -1,4 +1,4 @@
-import { Box, Collapse, IconButton, Paper, Stack, Typography, Tooltip, Avatar } from "@mui/material";
+import { Alert, Box, Collapse, IconButton, Paper, Stack, Typography, Tooltip, Avatar } from "@mui/material";
 import ExpandMoreIcon from '@mui/icons-material/ExpandMore';
 import ExpandLessIcon from '@mui/icons-material/ExpandLess';
 import React, { useEffect, useRef, useState } from "react";
@@ -400,7 +400,11 @@ export const MessagesList: React.FC<MessagesListProps> = ({ messages }) => {
                     )}
 
                     <MessageBubble sender={msg.sender}>
-                      {renderMessageText(msg.text)}
+                      {msg.status ? (
+                          <Alert severity={msg.status === 'blocked' ? 'warning' : 'error'} sx={{ whiteSpace: 'pre-wrap' }}>
+                            {msg.text}
+                          </Alert>
+                      ) : renderMessageText(msg.text)}
 
                       {hasMeta && metaData.length > 0 && (
                           <Box sx={{ mt: 1 }}>
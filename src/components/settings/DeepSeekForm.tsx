@@ -4,6 +4,7 @@ import { useTranslation } from 'react-i18next';
 
 interface DeepSeekFormProps {
   apiKey: string;
+  apiKeySet: boolean;
   setApiKey: (v: string) => void;
   deepseekUrl: string;
   setDeepseekUrl: (v: string) => void;
@@ -13,7 +14,7 @@ interface DeepSeekFormProps {
 
 const deepseekOptions = ['deepseek-chat', 'deepseek-reasoner'] as const;
 
-export const DeepSeekForm: React.FC<DeepSeekFormProps> = ({ apiKey, setApiKey, deepseekUrl, setDeepseekUrl, deepseekModel, setDeepseekModel }) => {
+export const DeepSeekForm: React.FC<DeepSeekFormProps> = ({ apiKey, apiKeySet, setApiKey, deepseekUrl, setDeepseekUrl, deepseekModel, setDeepseekModel }) => {
   const { t } = useTranslation();
   const [choice, setChoice] = useState<'deepseek-chat' | 'deepseek-reasoner' | 'custom'>(
     (deepseekOptions as readonly string[]).includes(deepseekModel) ? (deepseekModel as any) : 'custom'
@@ -65,7 +66,7 @@ export const DeepSeekForm: React.FC<DeepSeekFormProps> = ({ apiKey, setApiKey, d
         type="password"
         value={apiKey}
         onChange={(e) => setApiKey(e.target.value)}
-        placeholder={t('settings.apiKey') + ' (optional)' || ''}
+        placeholder={apiKeySet ? t('settings.apiKeySet') : t('settings.apiKey') + ' (optional)' || ''}
         size="small"
       />
     </>
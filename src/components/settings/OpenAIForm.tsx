@@ -8,12 +8,13 @@ interface OpenAIFormProps {
   apiModel: string;
   setApiModel: (v: string) => void;
   apiKey: string;
+  apiKeySet: boolean;
   setApiKey: (v: string) => void;
 }
 
 const openaiOptions = ['gpt-4o', 'gpt-4o-mini', 'gpt-4.1', 'gpt-4.1-mini'] as const;
 
-export const OpenAIForm: React.FC<OpenAIFormProps> = ({ apiBase, setApiBase, apiModel, setApiModel, apiKey, setApiKey }) => {
+export const OpenAIForm: React.FC<OpenAIFormProps> = ({ apiBase, setApiBase, apiModel, setApiModel, apiKey, apiKeySet, setApiKey }) => {
   const { t } = useTranslation();
   const [choice, setChoice] = useState<'gpt-4o' | 'gpt-4o-mini' | 'gpt-4.1' | 'gpt-4.1-mini' | 'custom'>(
     (openaiOptions as readonly string[]).includes(apiModel) ? (apiModel as any) : 'custom'
@@ -65,7 +66,7 @@ export const OpenAIForm: React.FC<OpenAIFormProps> = ({ apiBase, setApiBase, api
         type="password"
         value={apiKey}
         onChange={(e) => setApiKey(e.target.value)}
-        placeholder={t('settings.apiKey') + ' (optional)' || ''}
+        placeholder={apiKeySet ? t('settings.apiKeySet') : t('settings.apiKey') + ' (optional)' || ''}
         size="small"
       />
     </>
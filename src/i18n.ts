@@ -18,6 +18,7 @@ const resources = {
         sendingPlaceholder: 'Введите сообщение',
         startingServer: 'Запускаем сервер...',
         send: 'Отправить',
+        stop: 'Остановить',
         localModel: 'Локальная модель',
         startServer: 'Запустить сервер',
         serverStarting: 'Сервер запускается',
@@ -36,6 +37,7 @@ const resources = {
         apiBaseUrl: 'API Base URL',
         apiModel: 'Модель',
         apiKey: 'API Key',
+        apiKeySet: 'Ключ сохранён — введите новый, чтобы заменить',
         customModel: 'Своя модель',
         repoLabel: 'Репозиторий модели (GGUF)',
         repoPlaceholder: 'Например: bartowski/Llama-3.2-3B-Instruct-GGUF:Q8_0',
@@ -254,6 +256,7 @@ const resources = {
         sendingPlaceholder: 'Type a message',
         startingServer: 'Starting server...',
         send: 'Send',
+        stop: 'Stop',
         localModel: 'Local model',
         startServer: 'Start server',
         serverStarting: 'Server is starting…',
@@ -272,6 +275,7 @@ const resources = {
         apiBaseUrl: 'API Base URL',
         apiModel: 'Model',
         apiKey: 'API Key',
+        apiKeySet: 'Key saved — type a new one to replace it',
         customModel: 'Custom model',
         repoLabel: 'Model repository (GGUF)',
         repoPlaceholder: 'e.g., ggml-org/gemma-2-2b-it-GGUF',
@@ -1,6 +1,7 @@
 import { invoke } from '@tauri-apps/api/core';
+import type { CloudChatMessage } from './openai';
 
-export async function chatDeepseek(params: { apiKey: string; baseUrl: string; model: string; prompt: string; }): Promise<string> {
-  const { apiKey, baseUrl, model, prompt } = params;
-  return await invoke<string>('query_deepseek', { apiKey, baseUrl, model, prompt });
+export async function chatDeepseek(params: { apiKey: string; baseUrl: string; model: string; prompt: string; messages?: CloudChatMessage[]; temperature?: number; top_p?: number; max_tokens?: number; projectId?: number | null; requestId?: string; }): Promise<string> {
+  const { apiKey, baseUrl, model, prompt, messages, temperature, top_p, max_tokens, projectId, requestId } = params;
+  return await invoke<string>('query_deepseek', { apiKey, baseUrl, model, prompt, messages: messages ?? null, temperature, topP: top_p, maxTokens: max_tokens, projectId: projectId ?? null, requestId: requestId ?? null });
 }
@@ -0,0 +1,60 @@
+// Rejection value of generation commands (`ProviderError` in the backend)
+export type ProviderError =
+  | { kind: 'auth'; status: number; message: string }
+  | { kind: 'rate_limited'; message: string; retry_after?: number | null }
+  | { kind: 'context_length'; message: string }
+  | { kind: 'server'; status: number; message: string }
+  | { kind: 'network'; message: string }
+  | { kind: 'http'; status: number; message: string }
+  | { kind: 'invalid_response'; message: string }
+  | { kind: 'prompt_blocked'; reason: string }
+  | { kind: 'response_blocked'; reason: string; categories: string[] }
+  | { kind: 'structured_output'; error: StructuredOutputError }
+  | { kind: 'cancelled' }
+  | { kind: 'other'; message: string };
+
+// Why an answer was rejected by `response_schema` validation
+export type StructuredOutputError =
+  | { kind: 'invalid_json'; message: string; raw: string }
+  | { kind: 'schema_mismatch'; violations: { path: string; message: string }[]; raw: string };
+
+export function isProviderError(e: unknown): e is ProviderError {
+  return typeof e === 'object' && e !== null && typeof (e as any).kind === 'string';
+}
+
+export function describeError(e: unknown): string {
+  if (!isProviderError(e)) {
+    if (e instanceof Error) return e.message;
+    return String(e);
+  }
+  switch (e.kind) {
+    case 'auth':
+      return `Ошибка авторизации (HTTP ${e.status}): ${e.message}`;
+    case 'rate_limited':
+      return e.retry_after != null
+        ? `Превышен лимит запросов, повторите через ${e.retry_after} с: ${e.message}`
+        : `Превышен лимит запросов: ${e.message}`;
+    case 'context_length':
+      return `Запрос не помещается в контекст модели: ${e.message}`;
+    case 'server':
+      return `Ошибка сервера (HTTP ${e.status}): ${e.message}`;
+    case 'network':
+      return `Сетевая ошибка: ${e.message}`;
+    case 'http':
+      return `HTTP ${e.status}: ${e.message}`;
+    case 'invalid_response':
+      return `Некорректный ответ: ${e.message}`;
+    case 'prompt_blocked':
+      return `Запрос заблокирован фильтром безопасности: ${e.reason}`;
+    case 'response_blocked':
+      return `Ответ заблокирован фильтром безопасности: ${e.reason}${e.categories.length ? ` (${e.categories.join(', ')})` : ''}`;
+    case 'structured_output':
+      return e.error.kind === 'invalid_json'
+        ? `Ответ не является корректным JSON: ${e.error.message}`
+        : `Ответ не соответствует схеме: ${e.error.violations.map((v) => `${v.path}: ${v.message}`).join('; ')}`;
+    case 'cancelled':
+      return 'Генерация остановлена';
+    case 'other':
+      return e.message;
+  }
+}
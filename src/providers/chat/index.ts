@@ -2,3 +2,4 @@ export { chatDeepseek } from './deepseek';
 export { chatOpenAI } from './openai';
 export { chatOllama, type OllamaChatMessage, type OllamaChatResult } from './ollama';
 export { chatLlama } from './llama';
+export { describeError, isProviderError, type ProviderError, type StructuredOutputError } from './errors';
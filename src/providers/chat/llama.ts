@@ -13,6 +13,9 @@ export type LlamaGenParams = {
   max_tokens?: number;
   repeat_last_n?: number;
   messages?: LlamaChatMessage[];
+  projectId?: number | null;
+  // Lets `cancel_generation` stop this request
+  requestId?: string;
 };
 
 export async function chatLlama(params: LlamaGenParams): Promise<LlamaChatResult> {
@@ -27,6 +30,8 @@ export async function chatLlama(params: LlamaGenParams): Promise<LlamaChatResult
       system_fingerprint: data?.system_fingerprint,
       usage: data?.usage,
       timings: data?.timings,
+      // Resident models, when the request went through the llama-server router
+      router: data?.router,
     };
     return { content: String(content), meta, raw };
   } catch {
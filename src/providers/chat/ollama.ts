@@ -3,8 +3,8 @@ import { invoke } from '@tauri-apps/api/core';
 export type OllamaChatResult = { content: string; meta?: any; raw?: string };
 export type OllamaChatMessage = { role: 'system' | 'user' | 'assistant'; content: string };
 
-export async function chatOllama(params: { baseUrl: string; model: string; prompt?: string; messages?: OllamaChatMessage[]; temperature?: number; top_k?: number; top_p?: number; max_tokens?: number; advancedParams?: any }): Promise<OllamaChatResult> {
-  const { baseUrl, model, prompt, messages, temperature, top_k, top_p, max_tokens, advancedParams } = params as any;
+export async function chatOllama(params: { baseUrl: string; model: string; prompt?: string; messages?: OllamaChatMessage[]; temperature?: number; top_k?: number; top_p?: number; max_tokens?: number; advancedParams?: any; projectId?: number | null; requestId?: string }): Promise<OllamaChatResult> {
+  const { baseUrl, model, prompt, messages, temperature, top_k, top_p, max_tokens, advancedParams, projectId, requestId } = params as any;
 
   const modelName = String(model || '').toLowerCase();
   const isEmbeddingsOnly = /(embed|embedding)/i.test(modelName);
@@ -22,7 +22,7 @@ ollama.embeddings({ model: 'nomic-embed-text', prompt: 'The sky is blue because
     return { content: msg, meta: { model, note: 'embeddings_only' } };
   }
 
-  const raw = await invoke<string>('query_ollama', { baseUrl, model, prompt: prompt ?? '', messages: messages ?? null, temperature, top_k, top_p, max_tokens, advancedParams });
+  const raw = await invoke<string>('query_ollama', { baseUrl, model, prompt: prompt ?? '', messages: messages ?? null, temperature, top_k, top_p, max_tokens, advancedParams, projectId: projectId ?? null, requestId: requestId ?? null });
   try {
     const data = JSON.parse(raw);
     const content = data?.message?.content ?? data?.response ?? raw;
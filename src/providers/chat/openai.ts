@@ -1,6 +1,8 @@
 import { invoke } from '@tauri-apps/api/core';
 
-export async function chatOpenAI(params: { apiKey: string; baseUrl: string; model: string; prompt: string; }): Promise<string> {
-  const { apiKey, baseUrl, model, prompt } = params;
-  return await invoke<string>('query_openai', { apiKey, baseUrl, model, prompt });
+export type CloudChatMessage = { role: 'system' | 'user' | 'assistant'; content: string };
+
+export async function chatOpenAI(params: { apiKey: string; baseUrl: string; model: string; prompt: string; messages?: CloudChatMessage[]; temperature?: number; top_p?: number; max_tokens?: number; projectId?: number | null; requestId?: string; }): Promise<string> {
+  const { apiKey, baseUrl, model, prompt, messages, temperature, top_p, max_tokens, projectId, requestId } = params;
+  return await invoke<string>('query_openai', { apiKey, baseUrl, model, prompt, messages: messages ?? null, temperature, topP: top_p, maxTokens: max_tokens, projectId: projectId ?? null, requestId: requestId ?? null });
 }
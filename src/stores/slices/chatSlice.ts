@@ -1,14 +1,28 @@
 import { invoke } from '@tauri-apps/api/core';
 import { Message } from '../types';
-import { chatDeepseek, chatOpenAI, chatOllama, chatLlama } from '../../providers/chat';
+import { chatDeepseek, chatOpenAI, chatOllama, chatLlama, describeError, isProviderError } from '../../providers/chat';
 
 export interface ChatSlice {
   messages: Message[];
+  // Request id of the generation in flight, used by the Stop button
+  pendingRequestId: string | null;
   sendMessage: (text: string) => Promise<void>;
+  stopGeneration: () => Promise<void>;
 }
 
 export const createChatSlice = (set: any, get: any): ChatSlice => ({
   messages: [],
+  pendingRequestId: null,
+
+  stopGeneration: async () => {
+    const requestId = get().pendingRequestId;
+    if (!requestId) return;
+    try {
+      await invoke('cancel_generation', { requestId });
+    } catch (e) {
+      console.warn('cancel_generation failed', e);
+    }
+  },
 
   sendMessage: async (text: string) => {
     const { mode, apiKey, apiBase, apiModel, deepseekUrl, deepseekModel, isServerReady, ollamaBase, ollamaModel, activeProjectId, contextFolder } = get();
@@ -40,24 +54,33 @@ export const createChatSlice = (set: any, get: any): ChatSlice => ({
       }
     } catch {}
 
+    const requestId = crypto.randomUUID();
+    set({ pendingRequestId: requestId });
     try {
       let aiResponse: string;
       const promptToSend = folderContext ? `${folderContext}\n\n${text}` : text;
 
-      if (mode === 'deepseek') {
-        aiResponse = await chatDeepseek({
-          apiKey,
-          baseUrl: deepseekUrl,
-          model: deepseekModel,
-          prompt: promptToSend,
-        });
-      } else if (mode === 'openai') {
-        aiResponse = await chatOpenAI({
+      if (mode === 'deepseek' || mode === 'openai') {
+        // Build chat history for cloud APIs (limit last 20); folder context goes in as a system message
+        const allMsgs = get().messages;
+        const history = allMsgs.slice(-20).map((m: Message) => ({
+          role: m.sender === 'user' ? 'user' : 'assistant',
+          content: m.text,
+        }));
+        const historyWithCtx = folderContext ? ([{ role: 'system', content: folderContext } as any, ...history]) : history;
+        const params = {
           apiKey,
-          baseUrl: apiBase,
-          model: apiModel,
           prompt: promptToSend,
-        });
+          messages: historyWithCtx as any,
+          temperature: get().temperature,
+          top_p: get().topP,
+          max_tokens: get().maxTokens,
+          projectId: activeProjectId,
+          requestId,
+        };
+        aiResponse = mode === 'deepseek'
+          ? await chatDeepseek({ ...params, baseUrl: deepseekUrl, model: deepseekModel })
+          : await chatOpenAI({ ...params, baseUrl: apiBase, model: apiModel });
       } else if (mode === 'ollama') {
         // Build chat history for Ollama: map previous messages to roles (limit last 20)
         const allMsgs = get().messages;
@@ -80,6 +103,8 @@ export const createChatSlice = (set: any, get: any): ChatSlice => ({
           top_p: get().topP,
           max_tokens: get().maxTokens,
           advancedParams,
+          projectId: activeProjectId,
+          requestId,
         });
         set({ messages: [...get().messages, { text: res.content, sender: 'ai', meta: res.meta }] });
         return;
@@ -104,6 +129,8 @@ export const createChatSlice = (set: any, get: any): ChatSlice => ({
           max_tokens: get().maxTokens,
           repeat_last_n: get().repeatLastN,
           messages: historyWithCtx,
+          projectId: activeProjectId,
+          requestId,
         });
         // Push formatted content and metadata for llama.cpp
         set({ messages: [...get().messages, { text: res.content, sender: 'ai', meta: res.meta }] });
@@ -112,13 +139,17 @@ export const createChatSlice = (set: any, get: any): ChatSlice => ({
 
       set({ messages: [...get().messages, { text: aiResponse, sender: 'ai' }] });
     } catch (error) {
+      // Stopped by the user: nothing to report
+      if (isProviderError(error) && error.kind === 'cancelled') return;
       console.error('Error:', error);
-      set({
-        messages: [
-          ...get().messages,
-          { text: 'Ошибка: ' + (error as Error).message, sender: 'ai' },
-        ],
-      });
+      // Safety blocks are shown as warnings rather than failures
+      const blocked = isProviderError(error) && (error.kind === 'prompt_blocked' || error.kind === 'response_blocked');
+      const reply: Message = blocked
+        ? { text: describeError(error), sender: 'ai', status: 'blocked' }
+        : { text: 'Ошибка: ' + describeError(error), sender: 'ai', status: 'error' };
+      set({ messages: [...get().messages, reply] });
+    } finally {
+      if (get().pendingRequestId === requestId) set({ pendingRequestId: null });
     }
   },
 });
\ No newline at end of file
@@ -10,6 +10,7 @@ export type Project = {
   model: string;
   meta?: string | null;
   created_at: string;
+  provider_id?: number | null;
 };
 
 export interface ProjectsSlice {
@@ -1,6 +1,9 @@
 import { invoke } from '@tauri-apps/api/core';
 import { ChatMode, ModelSource } from '../types';
 
+// Placeholder the backend returns for a stored key; saving it back keeps that key
+const KEY_MASK = '••••••••';
+
 export interface SettingsSlice {
   // Persistence helpers
   loadSettings: () => Promise<void>;
@@ -8,6 +11,7 @@ export interface SettingsSlice {
 
   // API provider settings
   apiKey: string;
+  apiKeySet: boolean; // a key is stored and the field has not been edited
   apiBase: string; // for OpenAI-compatible providers (LocalAI, LM Studio, FastChat, Jan, rLLM, TensorRT-LLM)
   apiModel: string; // e.g., gpt-4o-mini
   deepseekUrl: string; // full endpoint url for DeepSeek
@@ -80,7 +84,9 @@ export const createSettingsSlice = (
       const s = await invoke<any>('load_settings');
       set({
         mode: (s.mode as any) || 'deepseek',
-        apiKey: s.api_key || '',
+        // The stored key never reaches the editable field, only whether there is one
+        apiKey: '',
+        apiKeySet: !!s.api_key,
         apiBase: s.api_base || 'https://api.openai.com/v1',
         apiModel: s.api_model || 'gpt-4o-mini',
         deepseekUrl: s.deepseek_url || 'https://api.deepseek.com/chat/completions',
@@ -115,7 +121,7 @@ export const createSettingsSlice = (
       await invoke('save_settings', {
         settings: {
           mode: s.mode,
-          api_key: s.apiKey,
+          api_key: s.apiKeySet ? KEY_MASK : s.apiKey,
           api_base: s.apiBase,
           api_model: s.apiModel,
           deepseek_url: s.deepseekUrl,
@@ -150,6 +156,7 @@ export const createSettingsSlice = (
 
   // API defaults
   apiKey: '',
+  apiKeySet: false,
   apiBase: 'https://api.openai.com/v1',
   apiModel: 'gpt-4o-mini',
   deepseekUrl: 'https://api.deepseek.com/chat/completions',
@@ -178,7 +185,7 @@ export const createSettingsSlice = (
   serverOS: 'windows',
 
   setApiKey: (key) => {
-    set({ apiKey: key });
+    set({ apiKey: key, apiKeySet: false });
     get().persistSettings();
   },
   setApiBase: (base) => {
@@ -1,5 +1,6 @@
 export type ChatMode = 'deepseek' | 'openai' | 'local' | 'ollama';
-export type Message = { text: string; sender: 'user' | 'ai'; meta?: any };
+// `status` marks AI messages that report a failure or a safety block instead of an answer
+export type Message = { text: string; sender: 'user' | 'ai'; meta?: any; status?: 'error' | 'blocked' };
 export type ModelSource = 'huggingface' | 'github' | 'gitlab' | 'direct';
 export type DownloadStatus = {
   progress: number;